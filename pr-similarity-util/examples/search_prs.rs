@@ -1,14 +1,19 @@
 use anyhow::Result;
 use pr_similarity_search::PRSearchIndex;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize the search index with the repository details
-    let mut search_index = PRSearchIndex::new("block".to_string(), "goose".to_string())?;
+    let mut search_index = PRSearchIndex::new(
+        "block".to_string(),
+        "goose".to_string(),
+        Path::new(".pr_cache"),
+    )?;
     
     println!("Loading the last 100 PRs...");
-    let loaded_prs = search_index.load_recent_prs(100).await?;
+    let loaded_prs = search_index.load_recent_prs(100, false).await?;
     
     // Count PRs by state
     let mut state_counts = HashMap::new();
@@ -0,0 +1,571 @@
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, ETAG, IF_NONE_MATCH, LINK, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+const JSON_MEDIA_TYPE: &str = "application/vnd.github+json";
+const CLIENT_USER_AGENT: &str = "Goose-PR-Analyzer/1.0";
+
+/// Size the response cache is kept under unless the caller picks another.
+pub const DEFAULT_RESPONSE_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// How hard to try before giving up on a request.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// First backoff delay for server errors and dropped connections,
+    /// doubled on each retry.
+    pub base_delay: Duration,
+    /// Longest backoff delay, however many retries have gone before.
+    pub max_backoff: Duration,
+    /// Wait after a secondary rate limit that gives no `Retry-After`;
+    /// GitHub asks for at least a minute.
+    pub secondary_limit_wait: Duration,
+    /// Longest single wait; a rate limit that resets later than this fails
+    /// the request instead of stalling the sync.
+    pub max_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            secondary_limit_wait: Duration::from_secs(60),
+            max_wait: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before retry number `attempt + 1`: `base_delay` doubled
+    /// per earlier retry, capped at `max_backoff`.
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt)
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+}
+
+/// HTTP access to the GitHub REST API. Every request goes through here so
+/// that authentication, retries, rate-limit waits and conditional requests
+/// are handled in one place.
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+    retry: RetryPolicy,
+    cache: Option<ResponseCache>,
+}
+
+impl ApiClient {
+    pub fn new(token: String, base_url: &str) -> Result<Self> {
+        Ok(Self {
+            http: reqwest::Client::builder().user_agent(CLIENT_USER_AGENT).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            retry: RetryPolicy::default(),
+            cache: None,
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Send requests to another API root, e.g. a GitHub Enterprise Server
+    /// at `https://ghe.example.com/api/v3`.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Keep responses that carry an ETag under `dir` and revalidate them
+    /// with `If-None-Match`. GitHub doesn't count 304 responses against the
+    /// rate limit, so repeated syncs of unchanged PRs are nearly free.
+    pub fn with_response_cache(self, dir: PathBuf) -> Self {
+        self.with_response_cache_limit(dir, DEFAULT_RESPONSE_CACHE_BYTES)
+    }
+
+    /// Like `with_response_cache`, evicting the least recently used
+    /// responses once the cache holds more than `max_bytes`.
+    pub fn with_response_cache_limit(mut self, dir: PathBuf, max_bytes: u64) -> Self {
+        self.cache = Some(ResponseCache::open(dir, max_bytes));
+        self
+    }
+
+    /// GET a route such as `/repos/block/goose/pulls` and decode the JSON body.
+    pub async fn get_json<T: DeserializeOwned>(&self, route: &str, params: &[(&str, String)]) -> Result<T> {
+        let body = self.get(route, params, JSON_MEDIA_TYPE).await?;
+        serde_json::from_str(&body).map_err(|e| anyhow!("unexpected response from {}: {}", route, e))
+    }
+
+    /// GET a route with a custom media type, e.g. `application/vnd.github.v3.diff`.
+    pub async fn get_text(&self, route: &str, accept: &str) -> Result<String> {
+        self.get(route, &[], accept).await
+    }
+
+    /// GET a route's raw body, such as a logs archive. Redirects are
+    /// followed; binary responses are not cached.
+    pub async fn get_bytes(&self, route: &str) -> Result<Vec<u8>> {
+        let url = self.request_url(route, &[])?;
+        let response = self.execute(&url, JSON_MEDIA_TYPE, None, None).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// GET one page of a list endpoint, along with the URL of the next page
+    /// from the `Link` header.
+    pub async fn get_page<T: DeserializeOwned>(&self, route: &str, params: &[(&str, String)]) -> Result<Page<T>> {
+        let url = self.request_url(route, params)?;
+        let response = self.send(url, JSON_MEDIA_TYPE, None).await?;
+        Ok(Page {
+            items: serde_json::from_str(&response.body)
+                .map_err(|e| anyhow!("unexpected response from {}: {}", route, e))?,
+            next: response.next,
+        })
+    }
+
+    /// GET every page of a list endpoint that returns a JSON array.
+    pub async fn get_all<T: DeserializeOwned>(&self, route: &str, params: &[(&str, String)]) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut page = self.get_page(route, &with_page_size(params)).await?;
+        loop {
+            items.append(&mut page.items);
+            match page.next {
+                Some(next) => page = self.get_page(&next, &[]).await?,
+                None => return Ok(items),
+            }
+        }
+    }
+
+    /// GET every page of a list endpoint that wraps its items in an object,
+    /// such as `{"total_count": 3, "workflow_runs": [...]}`. Paging stops
+    /// once `total_count` items have been read.
+    pub async fn get_all_in<T: DeserializeOwned>(
+        &self,
+        route: &str,
+        params: &[(&str, String)],
+        items_key: &str,
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut url = self.request_url(route, &with_page_size(params))?;
+        loop {
+            let response = self.send(url, JSON_MEDIA_TYPE, None).await?;
+            let mut object: Value = serde_json::from_str(&response.body)
+                .map_err(|e| anyhow!("unexpected response from {}: {}", route, e))?;
+            let total_count = object.get("total_count").and_then(Value::as_u64);
+            let page_items = object
+                .get_mut(items_key)
+                .map(Value::take)
+                .ok_or_else(|| anyhow!("response from {} has no '{}'", route, items_key))?;
+            let page_items: Vec<T> = serde_json::from_value(page_items)
+                .map_err(|e| anyhow!("unexpected response from {}: {}", route, e))?;
+            let empty = page_items.is_empty();
+            items.extend(page_items);
+
+            let complete = total_count.is_some_and(|total| items.len() as u64 >= total);
+            match response.next {
+                Some(next) if !complete && !empty => url = reqwest::Url::parse(&next)?,
+                _ => {
+                    if let Some(total) = total_count.filter(|&total| (items.len() as u64) < total) {
+                        eprintln!("{}: expected {} items, got {}", route, total, items.len());
+                    }
+                    return Ok(items);
+                }
+            }
+        }
+    }
+
+    /// Full URL for a route; absolute URLs are used as they are.
+    pub fn url(&self, route: &str) -> String {
+        if route.starts_with("http://") || route.starts_with("https://") {
+            route.to_string()
+        } else {
+            format!("{}/{}", self.base_url, route.trim_start_matches('/'))
+        }
+    }
+
+    /// Run a GraphQL query and decode its `data`. Queries that hit the
+    /// GraphQL rate limit are retried like rate-limited REST requests.
+    pub async fn graphql<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        #[derive(Deserialize)]
+        struct GraphQlResponse<T> {
+            data: Option<T>,
+            #[serde(default)]
+            errors: Vec<GraphQlError>,
+        }
+
+        #[derive(Deserialize)]
+        struct GraphQlError {
+            #[serde(rename = "type")]
+            kind: Option<String>,
+            message: String,
+        }
+
+        let url = reqwest::Url::parse(&self.graphql_url())?;
+        let request = serde_json::json!({ "query": query, "variables": variables });
+
+        let mut attempt = 0;
+        loop {
+            let body = self.send(url.clone(), JSON_MEDIA_TYPE, Some(&request)).await?.body;
+            let response: GraphQlResponse<T> = serde_json::from_str(&body)
+                .map_err(|e| anyhow!("unexpected GraphQL response: {}", e))?;
+
+            let rate_limited = response.errors.iter().any(|e| e.kind.as_deref() == Some("RATE_LIMITED"));
+            if rate_limited && attempt < self.retry.max_retries {
+                eprintln!(
+                    "GraphQL rate limit hit; waiting {}s before retrying",
+                    self.retry.secondary_limit_wait.as_secs()
+                );
+                tokio::time::sleep(self.retry.secondary_limit_wait).await;
+                attempt += 1;
+                continue;
+            }
+
+            // GitHub returns partial data alongside errors, e.g. for a PR
+            // number that doesn't exist; only fail when nothing came back
+            return match response.data {
+                Some(data) => Ok(data),
+                None => {
+                    let messages: Vec<_> = response.errors.into_iter().map(|e| e.message).collect();
+                    Err(anyhow!("GraphQL query failed: {}", messages.join("; ")))
+                }
+            };
+        }
+    }
+
+    /// GitHub Enterprise Server serves GraphQL at `/api/graphql`, next to
+    /// rather than under the REST root `/api/v3`.
+    fn graphql_url(&self) -> String {
+        match self.base_url.strip_suffix("/api/v3") {
+            Some(host) => format!("{}/api/graphql", host),
+            None => format!("{}/graphql", self.base_url),
+        }
+    }
+
+    async fn get(&self, route: &str, params: &[(&str, String)], accept: &str) -> Result<String> {
+        let url = self.request_url(route, params)?;
+        Ok(self.send(url, accept, None).await?.body)
+    }
+
+    fn request_url(&self, route: &str, params: &[(&str, String)]) -> Result<reqwest::Url> {
+        let url = self.url(route);
+        if params.is_empty() {
+            // `parse_with_params` would leave a dangling `?`
+            Ok(reqwest::Url::parse(&url)?)
+        } else {
+            Ok(reqwest::Url::parse_with_params(&url, params)?)
+        }
+    }
+
+    /// Send a GET, or a POST when there is a JSON `body`, retrying as the
+    /// `RetryPolicy` allows. Only GETs go through the response cache.
+    async fn send(&self, url: reqwest::Url, accept: &str, body: Option<&Value>) -> Result<RawResponse> {
+        let cache_key = format!("{} {}", accept, url);
+        let cached = match body {
+            Some(_) => None,
+            None => self.cache.as_ref().and_then(|cache| cache.get(&cache_key)),
+        };
+
+        let response = self
+            .execute(&url, accept, body, cached.as_ref().map(|entry| entry.etag.as_str()))
+            .await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entry) = cached {
+                return Ok(RawResponse {
+                    body: entry.body,
+                    next: entry.next,
+                });
+            }
+        }
+
+        let etag = header_str(response.headers(), ETAG.as_str()).map(String::from);
+        let next = header_str(response.headers(), LINK.as_str()).and_then(next_link);
+        let response = RawResponse {
+            body: response.text().await?,
+            next,
+        };
+        if let (Some(cache), Some(etag), None) = (&self.cache, etag, body) {
+            cache.put(&cache_key, &etag, &response)?;
+        }
+        Ok(response)
+    }
+
+    /// Send one request until it succeeds, or answers 304 to `etag`.
+    async fn execute(
+        &self,
+        url: &reqwest::Url,
+        accept: &str,
+        body: Option<&Value>,
+        etag: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let request = match body {
+                Some(body) => self.http.post(url.clone()).json(body),
+                None => self.http.get(url.clone()),
+            };
+            let mut request = request
+                .header(ACCEPT, accept)
+                .header(AUTHORIZATION, format!("Bearer {}", self.token));
+            if let Some(etag) = etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) if attempt < self.retry.max_retries => {
+                    self.backoff(attempt, &format!("{} failed: {}", url, e)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let status = response.status();
+            if status.is_success() || (status == StatusCode::NOT_MODIFIED && etag.is_some()) {
+                return Ok(response);
+            }
+
+            let headers = response.headers().clone();
+            let text = response.text().await.unwrap_or_default();
+            if attempt < self.retry.max_retries {
+                if let Some(wait) = self.rate_limit_wait(status, &headers, &text) {
+                    if wait > self.retry.max_wait {
+                        return Err(anyhow!(
+                            "GitHub rate limit for {} resets in {}s, longer than the {}s we are willing to wait",
+                            url,
+                            wait.as_secs(),
+                            self.retry.max_wait.as_secs()
+                        ));
+                    }
+                    eprintln!("Rate limited by GitHub; waiting {}s before retrying {}", wait.as_secs(), url);
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                    continue;
+                }
+                if status.is_server_error() {
+                    self.backoff(attempt, &format!("{} returned {}", url, status)).await;
+                    attempt += 1;
+                    continue;
+                }
+            }
+
+            return Err(anyhow!("GitHub API request {} failed with {}: {}", url, status, error_message(&text)));
+        }
+    }
+
+    /// How long to wait before retrying a rate-limited response, or `None`
+    /// if the response is not a rate limit (e.g. a 403 for missing access).
+    fn rate_limit_wait(&self, status: StatusCode, headers: &HeaderMap, body: &str) -> Option<Duration> {
+        if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+            return None;
+        }
+
+        if let Some(seconds) = header_str(headers, RETRY_AFTER.as_str()).and_then(|v| v.parse().ok()) {
+            return Some(Duration::from_secs(seconds));
+        }
+
+        if header_str(headers, "x-ratelimit-remaining") == Some("0") {
+            if let Some(reset) = header_str(headers, "x-ratelimit-reset").and_then(|v| v.parse::<i64>().ok()) {
+                let seconds = (reset - chrono::Utc::now().timestamp()).max(0) as u64;
+                // One extra second covers clock skew around the reset
+                return Some(Duration::from_secs(seconds + 1));
+            }
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS || body.to_lowercase().contains("rate limit") {
+            return Some(self.retry.secondary_limit_wait);
+        }
+        None
+    }
+
+    async fn backoff(&self, attempt: u32, reason: &str) {
+        let delay = self.retry.backoff_delay(attempt);
+        eprintln!("{}; retrying in {}ms", reason, delay.as_millis());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// The REST API root for a URL given by the user: API roots are kept as
+/// they are, `https://github.com` becomes `https://api.github.com`, and any
+/// other bare host is taken to be a GitHub Enterprise Server, whose API
+/// lives under `/api/v3`.
+pub fn api_base_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let host = url.split_once("://").map_or(url, |(_, rest)| rest);
+    if host.contains('/') || host.starts_with("api.") {
+        url.to_string()
+    } else if host == "github.com" {
+        DEFAULT_API_URL.to_string()
+    } else {
+        format!("{}/api/v3", url)
+    }
+}
+
+/// One page of a list endpoint.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// URL of the following page, if there is one.
+    pub next: Option<String>,
+}
+
+/// A successful response body and its next-page link.
+struct RawResponse {
+    body: String,
+    next: Option<String>,
+}
+
+/// Ask for the largest page GitHub allows, unless the caller chose a size.
+fn with_page_size<'a>(params: &[(&'a str, String)]) -> Vec<(&'a str, String)> {
+    let mut params = params.to_vec();
+    if !params.iter().any(|(name, _)| *name == "per_page") {
+        params.push(("per_page", "100".to_string()));
+    }
+    params
+}
+
+/// The `rel="next"` URL of a `Link` header, e.g.
+/// `<https://api.github.com/repositories/1/pulls?page=2>; rel="next", <...>; rel="last"`.
+pub fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#)
+            .then(|| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// The `message` of a GitHub error body, or the body itself.
+fn error_message(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        message: String,
+    }
+
+    serde_json::from_str::<ErrorBody>(body)
+        .map(|error| error.message)
+        .unwrap_or_else(|_| body.trim().to_string())
+}
+
+/// Responses stored on disk by request, for ETag revalidation. Entries
+/// are evicted least recently used first when the cache outgrows its limit.
+struct ResponseCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Bytes on disk, as of the last prune plus what was written since.
+    size: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    key: String,
+    etag: String,
+    body: String,
+    #[serde(default)]
+    next: Option<String>,
+}
+
+impl ResponseCache {
+    fn open(dir: PathBuf, max_bytes: u64) -> Self {
+        let cache = ResponseCache {
+            dir,
+            max_bytes,
+            size: AtomicU64::new(0),
+        };
+        cache.prune();
+        cache
+    }
+
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.path(key);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let entry: CachedResponse = serde_json::from_str(&contents).ok()?;
+        // Guard against the (unlikely) hash collision
+        if entry.key != key {
+            return None;
+        }
+        // The modification time is what eviction goes by
+        let _ = std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(entry)
+    }
+
+    fn put(&self, key: &str, etag: &str, response: &RawResponse) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = CachedResponse {
+            key: key.to_string(),
+            etag: etag.to_string(),
+            body: response.body.clone(),
+            next: response.next.clone(),
+        };
+        let contents = serde_json::to_string(&entry)?;
+        std::fs::write(self.path(key), &contents)?;
+        // Overwritten entries are counted twice until the next prune
+        let size = self.size.fetch_add(contents.len() as u64, Ordering::Relaxed) + contents.len() as u64;
+        if size > self.max_bytes {
+            self.prune();
+        }
+        Ok(())
+    }
+
+    /// Delete the least recently used entries until the cache is under
+    /// three quarters of its limit, so that pruning isn't needed again
+    /// after every write.
+    fn prune(&self) {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = dir
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let path = entry.path();
+                let is_entry = metadata.is_file() && path.extension().is_some_and(|ext| ext == "json");
+                is_entry.then(|| (metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), metadata.len(), path))
+            })
+            .collect();
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if size > self.max_bytes {
+            entries.sort();
+            let target = self.max_bytes / 4 * 3;
+            for (_, len, path) in entries {
+                if size <= target {
+                    break;
+                }
+                if std::fs::remove_file(&path).is_ok() {
+                    size -= len;
+                }
+            }
+        }
+        self.size.store(size, Ordering::Relaxed);
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(key)))
+    }
+}
+
+/// FNV-1a, for file names that stay the same across builds.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
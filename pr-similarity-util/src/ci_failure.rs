@@ -0,0 +1,539 @@
+//! Find what broke in failed CI runs by reading their GitHub Actions logs.
+//!
+//! A logs archive holds one `N_<job>.txt` file per job at the top level and,
+//! usually, a `<job>/N_<step>.txt` file per step. Every line starts with a
+//! timestamp, and the runner marks the failing step with `##[error]`.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::github::{Check, CheckSource, GitHubClient, PullRequestDetails};
+use crate::zip::{read_zip, ZipEntry};
+
+/// Lines of log kept after the line that opens an error block.
+pub const DEFAULT_CONTEXT: usize = 10;
+
+/// Lines of log kept before the start of an error block.
+const LINES_BEFORE: usize = 2;
+
+/// Commits before a PR's head that are checked for an earlier failure.
+const MAX_COMMITS_SCANNED: usize = 5;
+
+/// Why one failed check failed, as far as its logs tell.
+#[derive(Debug, Clone, Serialize)]
+pub struct CiFailure {
+    /// Workflow name, or the check name for non-Actions checks.
+    pub workflow: String,
+    pub source: CheckSource,
+    /// The commit the check ran on.
+    pub commit_sha: String,
+    pub url: String,
+    pub job: Option<String>,
+    pub step: Option<String>,
+    /// First `path:line` mentioned in the error block.
+    pub location: Option<String>,
+    /// Log lines around the error, without timestamps.
+    pub excerpt: Vec<String>,
+}
+
+/// The CI failures on a PR's head commit.
+#[derive(Debug, Serialize)]
+pub struct CiFailureReport {
+    pub repo: String,
+    pub pr_number: u64,
+    pub head_sha: String,
+    pub failures: Vec<CiFailure>,
+}
+
+/// The failing job of a run's logs archive.
+#[derive(Debug, Clone)]
+pub struct JobFailure {
+    pub job: String,
+    pub step: Option<String>,
+    pub location: Option<String>,
+    pub excerpt: Vec<String>,
+}
+
+/// Collect the failed checks of a PR's head commit and, for Actions runs,
+/// what their logs say went wrong.
+pub async fn find_ci_failures(client: &GitHubClient, pr_number: u64, context: usize) -> Result<CiFailureReport> {
+    let head_sha = client.get_head_sha(pr_number).await?;
+    let checks = client.get_checks(&head_sha).await?;
+    Ok(CiFailureReport {
+        repo: client.full_name(),
+        pr_number,
+        head_sha,
+        failures: failures_for_checks(client, &checks, context).await,
+    })
+}
+
+/// One entry per failing job of each failed check. Checks whose logs can't
+/// be read still get an entry, without job details.
+pub async fn failures_for_checks(client: &GitHubClient, checks: &[Check], context: usize) -> Vec<CiFailure> {
+    let mut failures = Vec::new();
+    for check in checks.iter().filter(|c| c.failed()) {
+        let jobs = if check.logs_url.is_some() {
+            match client.download_logs(check).await.and_then(|archive| read_zip(&archive)) {
+                Ok(entries) => extract_failures(&entries, context),
+                Err(e) => {
+                    eprintln!("Skipping logs of {} ({}): {}", check.name, check.id, e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let failure = CiFailure {
+            workflow: check.name.clone(),
+            source: check.source,
+            commit_sha: check.head_sha.clone(),
+            url: check.details_url.clone(),
+            job: None,
+            step: None,
+            location: None,
+            excerpt: Vec::new(),
+        };
+        if jobs.is_empty() {
+            failures.push(failure);
+            continue;
+        }
+        for job in jobs {
+            failures.push(CiFailure {
+                job: Some(job.job),
+                step: job.step,
+                location: job.location,
+                excerpt: job.excerpt,
+                ..failure.clone()
+            });
+        }
+    }
+    failures
+}
+
+/// The failures of the most recent commit before a PR's head whose CI
+/// failed, if the head itself passed: what the later commits fixed. Only
+/// failures with log excerpts are kept, since the rest have nothing to
+/// match against.
+pub async fn resolved_failures(client: &GitHubClient, pr: &PullRequestDetails) -> Vec<CiFailure> {
+    if pr.checks.iter().any(Check::failed) {
+        return Vec::new();
+    }
+    for commit in pr.commits.iter().rev().skip(1).take(MAX_COMMITS_SCANNED) {
+        let checks = match client.get_checks(&commit.sha).await {
+            Ok(checks) => checks,
+            Err(e) => {
+                eprintln!("Skipping CI history of {}#{}: {:#}", pr.repo, pr.number, e);
+                return Vec::new();
+            }
+        };
+        if checks.iter().any(Check::failed) {
+            let mut failures = failures_for_checks(client, &checks, DEFAULT_CONTEXT).await;
+            failures.retain(|f| !f.excerpt.is_empty());
+            return failures;
+        }
+    }
+    Vec::new()
+}
+
+/// Normalize a log excerpt so that the same failure on another run, commit
+/// or machine reads the same: timestamps, hashes, ids and numbers are
+/// masked, and paths are cut down to their file name.
+pub fn failure_signature(excerpt: &str) -> String {
+    excerpt
+        .lines()
+        .map(|line| {
+            strip_ansi(strip_timestamp(line))
+                .split_whitespace()
+                .filter_map(normalize_token)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty() && !line.starts_with("##[group]") && !line.starts_with("##[endgroup]"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Normalize one whitespace-separated token, keeping the punctuation
+/// around it. Returns `None` for tokens that carry no signal, like clock
+/// times.
+fn normalize_token(token: &str) -> Option<String> {
+    let core_start = token.find(|c: char| c.is_alphanumeric() || matches!(c, '/' | '\\' | '.' | '_'));
+    let Some(core_start) = core_start else {
+        return Some(token.to_string());
+    };
+    let core_end = token
+        .rfind(|c: char| c.is_alphanumeric() || c == '_')
+        .map_or(token.len(), |i| i + token[i..].chars().next().unwrap().len_utf8())
+        .max(core_start);
+    let (prefix, core, suffix) = (&token[..core_start], &token[core_start..core_end], &token[core_end..]);
+
+    let core = if is_timestamp(core) {
+        return None;
+    } else if core.contains("://") {
+        core.to_string()
+    } else if core.contains('/') || core.contains('\\') {
+        // Runner and checkout directories differ between machines
+        let file = core.rsplit(['/', '\\']).next().unwrap_or(core);
+        strip_line_number(file).to_string()
+    } else if is_hash(core) {
+        "<hash>".to_string()
+    } else if is_number(core) {
+        "<n>".to_string()
+    } else {
+        strip_line_number(core).to_string()
+    };
+    Some(format!("{}{}{}", prefix, core, suffix))
+}
+
+/// `agent.rs:42:9` -> `agent.rs`.
+fn strip_line_number(path: &str) -> &str {
+    match path.split_once(':') {
+        Some((file, position))
+            if file.contains('.') && position.split(':').all(|n| n.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            file
+        }
+        _ => path,
+    }
+}
+
+/// Dates and clock times, e.g. `2024-05-01T10:00:00Z` or `10:00:00.123`.
+fn is_timestamp(token: &str) -> bool {
+    let digits = token.chars().filter(|c| c.is_ascii_digit()).count();
+    digits >= 4
+        && (token.contains(':') || token.matches('-').count() == 2)
+        && token.chars().all(|c| c.is_ascii_digit() || matches!(c, ':' | '-' | '.' | 'T' | 'Z' | '+'))
+}
+
+/// Commit SHAs, UUIDs, memory addresses and similar ids. Short ones must
+/// mix letters and digits the way random hex does, so that words which
+/// happen to be hex, like `decade2024`, are kept.
+fn is_hash(token: &str) -> bool {
+    let token = token.strip_prefix("0x").unwrap_or(token);
+    let hex: Vec<char> = token.chars().filter(|&c| c != '-').collect();
+    // Switches between letters and digits
+    let switches = hex.windows(2).filter(|pair| pair[0].is_ascii_digit() != pair[1].is_ascii_digit()).count();
+    hex.len() >= 7
+        && hex.iter().all(|c| c.is_ascii_hexdigit())
+        && hex.iter().any(|c| c.is_ascii_digit())
+        && (hex.len() >= 12 || switches >= 2)
+}
+
+/// Counts, sizes and durations, e.g. `101`, `0.52s`, `250ms`.
+fn is_number(token: &str) -> bool {
+    let number = token.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    !number.is_empty()
+        && token.len() - number.len() <= 3
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && number.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Find the failing step of every job in a logs archive, keeping `context`
+/// lines from the start of its error block.
+pub fn extract_failures(entries: &[ZipEntry], context: usize) -> Vec<JobFailure> {
+    let mut failures = Vec::new();
+    for entry in entries.iter().filter(|e| !e.name.contains('/') && e.name.ends_with(".txt")) {
+        let lines = log_lines(&entry.contents);
+        let Some(error) = lines.iter().position(|line| line.starts_with("##[error]")) else {
+            continue;
+        };
+        let job = log_name(&entry.name);
+
+        // Steps start with a `Run <command>` group
+        let step_start = lines[..error].iter().rposition(|line| line.starts_with("##[group]Run "));
+        let step = step_name(entries, &job)
+            .or_else(|| step_start.and_then(|i| lines[i].strip_prefix("##[group]")).map(String::from));
+
+        let body = step_start.map_or(0, |i| i + 1)..error;
+        let (start, end) = match lines[body.clone()].iter().position(|line| is_error_line(line)) {
+            Some(i) => {
+                let anchor = body.start + i;
+                (anchor.saturating_sub(LINES_BEFORE).max(body.start), (anchor + 1 + context).min(error))
+            }
+            None => (error.saturating_sub(context).max(body.start), error),
+        };
+        let mut excerpt: Vec<String> = lines[start..end]
+            .iter()
+            .filter(|line| !line.starts_with("##[group]") && !line.starts_with("##[endgroup]"))
+            .cloned()
+            .collect();
+        excerpt.push(lines[error].clone());
+
+        failures.push(JobFailure {
+            job,
+            step,
+            location: excerpt.iter().find_map(|line| find_location(line)),
+            excerpt,
+        });
+    }
+    failures
+}
+
+/// The name of the step log under `job/` that holds the error, if the
+/// archive has per-step logs.
+fn step_name(entries: &[ZipEntry], job: &str) -> Option<String> {
+    let prefix = format!("{}/", job);
+    entries
+        .iter()
+        .filter(|e| e.name.starts_with(&prefix))
+        .find(|e| log_lines(&e.contents).iter().any(|line| line.starts_with("##[error]")))
+        .map(|e| log_name(&e.name[prefix.len()..]))
+}
+
+/// `3_Run tests.txt` -> `Run tests`.
+fn log_name(file_name: &str) -> String {
+    let name = file_name.trim_end_matches(".txt");
+    match name.split_once('_') {
+        Some((index, rest)) if index.chars().all(|c| c.is_ascii_digit()) => rest.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// A log's lines without timestamps or terminal colors.
+fn log_lines(contents: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(contents)
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| strip_ansi(strip_timestamp(line)))
+        .collect()
+}
+
+/// Drop the `2024-05-01T10:00:00.1234567Z ` prefix the runner adds.
+fn strip_timestamp(line: &str) -> &str {
+    match line.split_once(' ') {
+        Some((stamp, rest)) if stamp.len() >= 20 && stamp.ends_with('Z') && stamp.as_bytes()[10] == b'T' => rest,
+        _ => line,
+    }
+}
+
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip `ESC [ params final-byte`
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Lines that typically open a compiler, test or tool error.
+fn is_error_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("error")
+        || line.starts_with("Error:")
+        || line.starts_with("ERROR")
+        || line.starts_with("FAIL ")
+        || line.starts_with("npm ERR!")
+        || line.contains("panicked at")
+        || line.contains("FAILED")
+}
+
+/// The first `path:line` in a line, e.g. from `--> src/lib.rs:12:5` or
+/// `panicked at crates/goose/src/agent.rs:40:9:`.
+pub fn find_location(line: &str) -> Option<String> {
+    line.split(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '`' | '(' | ')' | ','))
+        .find_map(|token| {
+            let mut parts = token.trim_end_matches(':').split(':');
+            let path = parts.next()?;
+            let line = parts.next()?;
+            let is_path = (path.contains('.') || path.contains('/'))
+                && path.chars().any(|c| c.is_alphabetic())
+                && !path.starts_with("http");
+            let is_line = !line.is_empty() && line.chars().all(|c| c.is_ascii_digit());
+            (is_path && is_line).then(|| format!("{}:{}", path, line))
+        })
+}
+
+/// `workflow / job / step`, as far as it is known.
+pub fn failure_title(failure: &CiFailure) -> String {
+    std::iter::once(&failure.workflow)
+        .chain(&failure.job)
+        .chain(&failure.step)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_the_same_across_runs() {
+        let first = "2024-05-01T10:00:00.1234567Z ##[group]Run cargo test\n\
+                     2024-05-01T10:00:01.0000000Z thread 'agent' panicked at /home/runner/work/goose/goose/crates/goose/src/agent.rs:40:9:\n\
+                     2024-05-01T10:00:01.0000000Z \u{1b}[31merror\u{1b}[0m: test failed after 0.52s in 5d2f0c9e1a7b\n";
+        let second = "thread 'agent' panicked at D:\\a\\goose\\crates\\goose\\src\\agent.rs:41:13:\n\
+                      \n\
+                      error: test failed after 1.7s in 0a9e8d7c6b5f\n";
+
+        assert_eq!(
+            failure_signature(first),
+            "thread 'agent' panicked at agent.rs:\nerror: test failed after <n> in <hash>"
+        );
+        assert_eq!(failure_signature(first), failure_signature(second));
+    }
+
+    #[test]
+    fn signature_drops_clock_times_and_groups() {
+        let excerpt = "##[group]Run npm test\nStarted at 10:00:00.123 on 2024-05-01\n##[endgroup]\n12:00:01";
+        assert_eq!(failure_signature(excerpt), "Started at on");
+    }
+
+    #[test]
+    fn tokens_keep_surrounding_punctuation() {
+        assert_eq!(normalize_token("(/home/runner/work/src/lib.rs:12:5)").as_deref(), Some("(lib.rs)"));
+        assert_eq!(normalize_token("'src/lib.rs'").as_deref(), Some("'lib.rs'"));
+        assert_eq!(normalize_token("agent.rs:42:9").as_deref(), Some("agent.rs"));
+        assert_eq!(normalize_token("[5d2f0c9]").as_deref(), Some("[<hash>]"));
+        assert_eq!(normalize_token("error[E0308]:").as_deref(), Some("error[E0308]:"));
+        assert_eq!(normalize_token("--").as_deref(), Some("--"));
+        assert_eq!(normalize_token("10:00:00,").as_deref(), None);
+    }
+
+    #[test]
+    fn urls_are_kept_whole() {
+        let url = "https://github.com/block/goose/actions/runs/4401";
+        assert_eq!(normalize_token(url).as_deref(), Some(url));
+    }
+
+    #[test]
+    fn numbers_and_durations() {
+        for token in ["101", "0.52s", "250ms", "1234567", "3KiB"] {
+            assert_eq!(normalize_token(token).as_deref(), Some("<n>"), "{}", token);
+        }
+        // Versions and identifiers that end in digits are not counts
+        for token in ["v1.2.3", "utf8", "E0308", "x86_64"] {
+            assert_eq!(normalize_token(token).as_deref(), Some(token), "{}", token);
+        }
+    }
+
+    #[test]
+    fn hashes() {
+        for token in [
+            "5d2f0c9",
+            "5d2f0c9e1a7b3c4d5e6f708192a3b4c5d6e7f809",
+            "0x7ffd5e8b",
+            "550e8400-e29b-41d4-a716-446655440000",
+            "171455760000",
+        ] {
+            assert!(is_hash(token), "{}", token);
+        }
+        // Words made of hex letters, alone or followed by a number
+        for token in ["deadbeef", "decade2024", "cafe123", "facade", "1234567"] {
+            assert!(!is_hash(token), "{}", token);
+        }
+    }
+
+    #[test]
+    fn timestamps() {
+        for token in ["2024-05-01", "2024-05-01T10:00:00Z", "10:00:00.123", "12:00", "2024-05-01T10:00:00+02:00"] {
+            assert!(is_timestamp(token), "{}", token);
+        }
+        for token in ["1-2-3", "2024", "12:5", "v2024-05-01", "main.rs:1234"] {
+            assert!(!is_timestamp(token), "{}", token);
+        }
+    }
+
+    fn entry(name: &str, lines: &[&str]) -> ZipEntry {
+        let contents: String = lines
+            .iter()
+            .map(|line| format!("2024-05-01T10:00:00.1234567Z {}\n", line))
+            .collect();
+        ZipEntry {
+            name: name.to_string(),
+            contents: format!("\u{feff}{}", contents).into_bytes(),
+        }
+    }
+
+    const TEST_LOG: [&str; 9] = [
+        "##[group]Run actions/checkout@v4",
+        "Fetching the repository",
+        "##[endgroup]",
+        "##[group]Run cargo test",
+        "running 12 tests",
+        "test resize::hidpi ... FAILED",
+        "thread 'resize::hidpi' panicked at src/resize.rs:31:5:",
+        "assertion failed: scale > 1",
+        "##[error]Process completed with exit code 101.",
+    ];
+
+    #[test]
+    fn job_logs_without_step_logs() {
+        let entries = [entry("1_build.txt", &TEST_LOG), entry("2_lint.txt", &["All good"])];
+
+        let failures = extract_failures(&entries, 1);
+
+        assert_eq!(failures.len(), 1);
+        let failure = &failures[0];
+        assert_eq!(failure.job, "build");
+        assert_eq!(failure.step.as_deref(), Some("Run cargo test"));
+        assert_eq!(failure.location.as_deref(), Some("src/resize.rs:31"));
+        // From up to two lines before the first error line, within the
+        // step, to `context` lines after it
+        assert_eq!(
+            failure.excerpt,
+            [
+                "running 12 tests",
+                "test resize::hidpi ... FAILED",
+                "thread 'resize::hidpi' panicked at src/resize.rs:31:5:",
+                "##[error]Process completed with exit code 101.",
+            ]
+        );
+    }
+
+    #[test]
+    fn step_logs_name_the_failing_step() {
+        let entries = [
+            entry("0_build.txt", &TEST_LOG),
+            entry("build/1_Set up job.txt", &["Runner ready"]),
+            entry("build/4_Test widgets.txt", &TEST_LOG[3..]),
+        ];
+
+        let failures = extract_failures(&entries, DEFAULT_CONTEXT);
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].job, "build");
+        assert_eq!(failures[0].step.as_deref(), Some("Test widgets"));
+        assert_eq!(failures[0].excerpt.last().map(String::as_str), Some(TEST_LOG[8]));
+    }
+
+    #[test]
+    fn excerpt_without_a_recognized_error_line() {
+        let log = [
+            "##[group]Run ./deploy.sh",
+            "uploading",
+            "connection reset",
+            "##[error]Process completed with exit code 1.",
+        ];
+        let failures = extract_failures(&[entry("3_deploy.txt", &log)], 1);
+
+        assert_eq!(failures[0].excerpt, ["connection reset", "##[error]Process completed with exit code 1."]);
+        assert_eq!(failures[0].location, None);
+    }
+
+    #[test]
+    fn locations() {
+        assert_eq!(find_location("  --> src/lib.rs:12:5").as_deref(), Some("src/lib.rs:12"));
+        assert_eq!(
+            find_location("panicked at crates/goose/src/agent.rs:40:9:").as_deref(),
+            Some("crates/goose/src/agent.rs:40")
+        );
+        assert_eq!(find_location("at Object.<anonymous> (App.test.tsx:8:3)").as_deref(), Some("App.test.tsx:8"));
+        assert_eq!(find_location("File \"tests/test_api.py\", line 3").as_deref(), None);
+        assert_eq!(find_location("see https://example.com:443/docs").as_deref(), None);
+        assert_eq!(find_location("finished at 10:00:01").as_deref(), None);
+        assert_eq!(find_location("error: 2 tests failed").as_deref(), None);
+    }
+}
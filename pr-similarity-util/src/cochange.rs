@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// PRs touching more files than this are left out of the graph: sweeping
+/// renames and formatting passes would otherwise link everything to everything.
+const MAX_FILES_PER_PR: usize = 50;
+
+/// How often pairs of files are changed in the same PR.
+#[derive(Debug, Default)]
+pub struct CoChangeGraph {
+    /// Number of PRs in the graph.
+    total_prs: usize,
+    /// Number of PRs touching each file.
+    file_counts: HashMap<String, usize>,
+    /// For each file, the number of PRs shared with each other file.
+    neighbors: HashMap<String, HashMap<String, usize>>,
+}
+
+/// A file that tends to change together with the queried one.
+#[derive(Debug, Serialize)]
+pub struct RelatedFile {
+    pub path: String,
+    /// Number of PRs that changed both files.
+    pub co_changes: usize,
+    /// Share of all PRs that changed both files.
+    pub support: f32,
+    /// Share of the PRs changing the queried file that also changed this one.
+    pub confidence: f32,
+}
+
+impl CoChangeGraph {
+    /// Build the graph from the changed-file lists of a set of PRs.
+    pub fn build<I>(prs: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<String>>,
+    {
+        let mut graph = Self::default();
+
+        for files in prs {
+            let files: BTreeSet<String> = files.into_iter().filter(|f| !f.is_empty()).collect();
+            if files.is_empty() || files.len() > MAX_FILES_PER_PR {
+                continue;
+            }
+            graph.total_prs += 1;
+
+            for file in &files {
+                *graph.file_counts.entry(file.clone()).or_insert(0) += 1;
+                let neighbors = graph.neighbors.entry(file.clone()).or_default();
+                for other in files.iter().filter(|other| *other != file) {
+                    *neighbors.entry(other.clone()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// Files most often changed alongside `path`, by confidence and then
+    /// by number of shared PRs.
+    pub fn related(&self, path: &str, limit: usize) -> Vec<RelatedFile> {
+        let (Some(neighbors), Some(&count)) = (self.neighbors.get(path), self.file_counts.get(path)) else {
+            return Vec::new();
+        };
+
+        let mut related: Vec<RelatedFile> = neighbors
+            .iter()
+            .map(|(other, &co_changes)| RelatedFile {
+                path: other.clone(),
+                co_changes,
+                support: co_changes as f32 / self.total_prs as f32,
+                confidence: co_changes as f32 / count as f32,
+            })
+            .collect();
+
+        related.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then(b.co_changes.cmp(&a.co_changes))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        related.truncate(limit);
+        related
+    }
+
+    /// Number of PRs that changed `path`.
+    pub fn change_count(&self, path: &str) -> usize {
+        self.file_counts.get(path).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn graph() -> CoChangeGraph {
+        CoChangeGraph::build(vec![
+            files(&["src/lib.rs", "src/github.rs"]),
+            files(&["src/lib.rs", "src/github.rs", "README.md"]),
+            files(&["src/lib.rs", "src/main.rs"]),
+            files(&["docs/index.md"]),
+        ])
+    }
+
+    #[test]
+    fn change_count_counts_each_pr_once() {
+        let graph = CoChangeGraph::build(vec![files(&["src/lib.rs", "src/lib.rs", ""])]);
+        assert_eq!(graph.change_count("src/lib.rs"), 1);
+        assert_eq!(graph.change_count(""), 0);
+        assert_eq!(graph.change_count("src/main.rs"), 0);
+    }
+
+    #[test]
+    fn related_ranks_by_confidence_then_shared_prs() {
+        let related = graph().related("src/lib.rs", 10);
+        let ranked: Vec<_> = related.iter().map(|r| (r.path.as_str(), r.co_changes)).collect();
+        assert_eq!(ranked, [("src/github.rs", 2), ("README.md", 1), ("src/main.rs", 1)]);
+
+        let github = &related[0];
+        assert_eq!(github.support, 0.5);
+        assert_eq!(github.confidence, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn related_is_asymmetric() {
+        // Every PR touching README.md also touched src/lib.rs, but not the other way round
+        let related = graph().related("README.md", 10);
+        assert!(related.iter().all(|r| r.confidence == 1.0), "{:?}", related);
+    }
+
+    #[test]
+    fn related_respects_limit_and_unknown_paths() {
+        let graph = graph();
+        assert_eq!(graph.related("src/lib.rs", 1).len(), 1);
+        assert!(graph.related("docs/index.md", 10).is_empty());
+        assert!(graph.related("missing.rs", 10).is_empty());
+    }
+
+    #[test]
+    fn sweeping_prs_are_left_out() {
+        let sweep: Vec<String> = (0..=MAX_FILES_PER_PR).map(|i| format!("src/file{}.rs", i)).collect();
+        let graph = CoChangeGraph::build(vec![sweep, files(&["src/file0.rs"])]);
+        assert_eq!(graph.change_count("src/file0.rs"), 1);
+        assert!(graph.related("src/file0.rs", 10).is_empty());
+    }
+}
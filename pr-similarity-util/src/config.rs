@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Indexed text fields that free-text queries are run against.
+pub const SEARCH_FIELDS: [&str; 7] = [
+    "title",
+    "description",
+    "files",
+    "diff",
+    "commits",
+    "comments",
+    "review_comments",
+];
+
+/// Search tuning that can be set from a JSON config file or the CLI.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Per-field score multipliers, keyed by field name.
+    pub field_boosts: BTreeMap<String, f32>,
+    /// Word-embedding file (GloVe / fastText `.vec` text format) that enables
+    /// semantic reranking of BM25 results.
+    pub embedding_model: Option<PathBuf>,
+    /// How much of the final score comes from embedding similarity, 0 to 1.
+    pub semantic_weight: f32,
+    /// Number of BM25 hits considered for semantic reranking.
+    pub rerank_candidates: usize,
+    /// Age in days at which a PR's score is halved. Unset means no time decay.
+    pub recency_half_life_days: Option<f32>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        let field_boosts = [
+            ("title", 2.0),
+            ("description", 1.0),
+            ("files", 1.5),
+            ("diff", 0.5),
+            ("commits", 1.0),
+            ("comments", 0.75),
+            ("review_comments", 1.0),
+        ]
+        .into_iter()
+        .map(|(field, boost)| (field.to_string(), boost))
+        .collect();
+
+        Self {
+            field_boosts,
+            embedding_model: None,
+            semantic_weight: 0.3,
+            rerank_candidates: 50,
+            recency_half_life_days: None,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Load a config file, filling in defaults for anything it leaves out.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let overrides: SearchConfig = serde_json::from_str(&contents)?;
+        // A partial `field_boosts` map overrides individual defaults
+        // rather than replacing the whole map
+        let mut config = SearchConfig {
+            field_boosts: Self::default().field_boosts,
+            ..overrides.clone()
+        };
+        for (field, boost) in overrides.field_boosts {
+            config.set_boost(&field, boost)?;
+        }
+        config.set_semantic_weight(config.semantic_weight)?;
+        if let Some(days) = config.recency_half_life_days {
+            config.set_recency_half_life(days)?;
+        }
+        Ok(config)
+    }
+
+    pub fn set_boost(&mut self, field: &str, boost: f32) -> Result<()> {
+        if !SEARCH_FIELDS.contains(&field) {
+            return Err(anyhow!(
+                "unknown search field '{}' (expected one of: {})",
+                field,
+                SEARCH_FIELDS.join(", ")
+            ));
+        }
+        if !boost.is_finite() || boost < 0.0 {
+            return Err(anyhow!("boost for '{}' must be a non-negative number", field));
+        }
+        self.field_boosts.insert(field.to_string(), boost);
+        Ok(())
+    }
+
+    pub fn set_semantic_weight(&mut self, weight: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(anyhow!("semantic weight must be between 0 and 1"));
+        }
+        self.semantic_weight = weight;
+        Ok(())
+    }
+
+    pub fn set_recency_half_life(&mut self, days: f32) -> Result<()> {
+        if !days.is_finite() || days <= 0.0 {
+            return Err(anyhow!("recency half-life must be a positive number of days"));
+        }
+        self.recency_half_life_days = Some(days);
+        Ok(())
+    }
+
+    pub fn boost(&self, field: &str) -> f32 {
+        self.field_boosts.get(field).copied().unwrap_or(1.0)
+    }
+}
+
+/// Parse a `field=boost` pair as given on the command line.
+pub fn parse_field_boost(s: &str) -> Result<(String, f32)> {
+    let (field, boost) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected FIELD=BOOST, got '{}'", s))?;
+    let boost = boost
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("invalid boost '{}' for field '{}'", boost, field))?;
+    Ok((field.trim().to_string(), boost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, json: &str) -> Result<SearchConfig> {
+        let path = std::env::temp_dir().join(format!("pr-similarity-config-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, json).unwrap();
+        let config = SearchConfig::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn load_overrides_individual_boosts() {
+        let config = load(
+            "partial",
+            r#"{"field_boosts": {"diff": 3.0}, "semantic_weight": 0.5, "recency_half_life_days": 90}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.boost("diff"), 3.0);
+        assert_eq!(config.boost("title"), 2.0);
+        assert_eq!(config.field_boosts.len(), SEARCH_FIELDS.len());
+        assert_eq!(config.semantic_weight, 0.5);
+        assert_eq!(config.rerank_candidates, 50);
+        assert_eq!(config.recency_half_life_days, Some(90.0));
+        assert_eq!(config.embedding_model, None);
+    }
+
+    #[test]
+    fn load_empty_object_gives_defaults() {
+        let config = load("empty", "{}").unwrap();
+        let defaults = SearchConfig::default();
+
+        assert_eq!(config.field_boosts, defaults.field_boosts);
+        assert_eq!(config.semantic_weight, defaults.semantic_weight);
+        assert_eq!(config.recency_half_life_days, None);
+    }
+
+    #[test]
+    fn load_rejects_unknown_keys() {
+        let err = load("typo", r#"{"field_boost": {"diff": 3.0}}"#).unwrap_err();
+        assert!(err.to_string().contains("unknown field `field_boost`"), "{}", err);
+    }
+
+    #[test]
+    fn load_rejects_invalid_values() {
+        assert!(load("field", r#"{"field_boosts": {"body": 1.0}}"#).is_err());
+        assert!(load("negative", r#"{"field_boosts": {"diff": -1.0}}"#).is_err());
+        assert!(load("weight", r#"{"semantic_weight": 1.5}"#).is_err());
+        assert!(load("half-life", r#"{"recency_half_life_days": 0}"#).is_err());
+        assert!(load("json", r#"{"field_boosts": "#).is_err());
+    }
+
+    #[test]
+    fn load_reports_missing_files() {
+        let path = std::env::temp_dir().join("pr-similarity-config-does-not-exist.json");
+        assert!(SearchConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn set_boost_checks_field_and_value() {
+        let mut config = SearchConfig::default();
+
+        config.set_boost("files", 0.0).unwrap();
+        assert_eq!(config.boost("files"), 0.0);
+
+        let err = config.set_boost("body", 1.0).unwrap_err();
+        assert!(err.to_string().starts_with("unknown search field 'body'"), "{}", err);
+        assert!(config.set_boost("diff", -0.5).is_err());
+        assert!(config.set_boost("diff", f32::NAN).is_err());
+        assert!(config.set_boost("diff", f32::INFINITY).is_err());
+        assert_eq!(config.boost("diff"), 0.5);
+    }
+
+    #[test]
+    fn parses_field_boosts() {
+        assert_eq!(parse_field_boost("title=3").unwrap(), ("title".to_string(), 3.0));
+        assert_eq!(parse_field_boost(" diff = 0.25 ").unwrap(), ("diff".to_string(), 0.25));
+        assert!(parse_field_boost("title").is_err());
+        assert!(parse_field_boost("title=").is_err());
+        assert!(parse_field_boost("title=high").is_err());
+    }
+}
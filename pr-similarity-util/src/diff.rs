@@ -0,0 +1,136 @@
+/// One `@@` hunk of a unified diff, with the file it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffHunk {
+    pub path: String,
+    pub old_start: u64,
+    pub old_lines: u64,
+    pub new_start: u64,
+    pub new_lines: u64,
+    /// The full `@@ -a,b +c,d @@ context` line.
+    pub header: String,
+    /// The hunk body, without its header.
+    pub content: String,
+}
+
+/// Split a `git diff` style patch into its hunks.
+pub fn parse_hunks(diff: &str) -> Vec<DiffHunk> {
+    let mut hunks = Vec::new();
+    let mut path: Option<String> = None;
+    let mut current: Option<DiffHunk> = None;
+
+    for line in diff.lines() {
+        if line.starts_with("diff --git") {
+            hunks.extend(current.take());
+            path = line.split(" b/").nth(1).map(String::from);
+        } else if line.starts_with("@@") {
+            hunks.extend(current.take());
+            if let (Some(path), Some((old_start, old_lines, new_start, new_lines))) =
+                (&path, parse_hunk_header(line))
+            {
+                current = Some(DiffHunk {
+                    path: path.clone(),
+                    old_start,
+                    old_lines,
+                    new_start,
+                    new_lines,
+                    header: line.to_string(),
+                    content: String::new(),
+                });
+            }
+        } else if let Some(hunk) = current.as_mut() {
+            hunk.content.push_str(line);
+            hunk.content.push('\n');
+        } else if let Some(new_path) = line.strip_prefix("+++ b/") {
+            path = Some(new_path.to_string());
+        }
+    }
+    hunks.extend(current);
+
+    hunks
+}
+
+/// Parse the ranges out of `@@ -old_start[,old_lines] +new_start[,new_lines] @@`.
+pub(crate) fn parse_hunk_header(line: &str) -> Option<(u64, u64, u64, u64)> {
+    let mut parts = line.trim_start_matches('@').split_whitespace();
+    let (old_start, old_lines) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_lines) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_lines, new_start, new_lines))
+}
+
+fn parse_range(range: &str) -> Option<(u64, u64)> {
+    match range.split_once(',') {
+        Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -10,3 +10,4 @@ impl Index {
+     fn open() {
+-        todo!()
++        let dir = cache_dir();
++        load(dir)
+     }
+@@ -40 +41 @@ fn close() {
+-    flush();
++    flush()?;
+diff --git a/README.md b/README.md
+new file mode 100644
+--- /dev/null
++++ b/README.md
+@@ -0,0 +1 @@
++# Index
+";
+
+    #[test]
+    fn parse_hunks_splits_files_and_hunks() {
+        let hunks = parse_hunks(DIFF);
+        let ranges: Vec<_> = hunks
+            .iter()
+            .map(|h| (h.path.as_str(), h.old_start, h.old_lines, h.new_start, h.new_lines))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                ("src/lib.rs", 10, 3, 10, 4),
+                ("src/lib.rs", 40, 1, 41, 1),
+                ("README.md", 0, 0, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_hunks_keeps_header_and_body() {
+        let hunks = parse_hunks(DIFF);
+        assert_eq!(hunks[0].header, "@@ -10,3 +10,4 @@ impl Index {");
+        assert_eq!(
+            hunks[0].content,
+            "     fn open() {\n-        todo!()\n+        let dir = cache_dir();\n+        load(dir)\n     }\n"
+        );
+        // The next file's header isn't part of the previous hunk
+        assert_eq!(hunks[1].content, "-    flush();\n+    flush()?;\n");
+    }
+
+    #[test]
+    fn parse_hunks_skips_malformed_headers() {
+        let diff = "diff --git a/a.rs b/a.rs\n@@ bogus @@\n+x\n@@ -1 +1 @@\n-y\n+z\n";
+        let hunks = parse_hunks(diff);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].content, "-y\n+z\n");
+    }
+
+    #[test]
+    fn parse_hunk_header_defaults_counts_to_one() {
+        assert_eq!(parse_hunk_header("@@ -7 +8,2 @@"), Some((7, 1, 8, 2)));
+        assert_eq!(parse_hunk_header("@@ -0,0 +1 @@ fn main()"), Some((0, 0, 1, 1)));
+        assert_eq!(parse_hunk_header("@@ +1 -1 @@"), None);
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::tokenizer::code_terms;
+
+const VECTOR_STORE_FILE: &str = "vectors.json";
+
+/// A local word-embedding model loaded from a text file in the GloVe /
+/// fastText `.vec` format: one `word v1 v2 ... vn` entry per line, with an
+/// optional `count dim` header line.
+///
+/// Text is embedded as the normalized mean of its word vectors. Everything
+/// runs on the CPU from the file on disk; nothing touches the network.
+pub struct EmbeddingModel {
+    name: String,
+    dim: usize,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl EmbeddingModel {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow!("failed to open embedding model {}: {}", path.display(), e))?;
+        let mut vectors = HashMap::new();
+        let mut dim = 0;
+
+        for (line_no, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else {
+                continue;
+            };
+            let values: Vec<f32> = parts.map(str::parse).collect::<Result<_, _>>().map_err(|_| {
+                anyhow!("{}:{}: invalid vector component", path.display(), line_no + 1)
+            })?;
+
+            // fastText files start with a "count dim" header
+            if line_no == 0 && values.len() == 1 {
+                continue;
+            }
+            if dim == 0 {
+                dim = values.len();
+            }
+            if values.len() != dim {
+                return Err(anyhow!(
+                    "{}:{}: expected {} dimensions, found {}",
+                    path.display(),
+                    line_no + 1,
+                    dim,
+                    values.len()
+                ));
+            }
+            vectors.insert(word.to_lowercase(), values);
+        }
+
+        if vectors.is_empty() {
+            return Err(anyhow!("embedding model {} has no vectors", path.display()));
+        }
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self { name, dim, vectors })
+    }
+
+    /// Identifies the model a stored vector was produced with.
+    pub fn id(&self) -> String {
+        format!("{}:{}", self.name, self.dim)
+    }
+
+    /// Embed `text` as a unit vector, or `None` if no word is in the vocabulary.
+    pub fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        let mut sum = vec![0.0f32; self.dim];
+        let mut found = 0;
+        for term in code_terms(text) {
+            if let Some(vector) = self.vectors.get(&term) {
+                for (s, v) in sum.iter_mut().zip(vector) {
+                    *s += v;
+                }
+                found += 1;
+            }
+        }
+        if found == 0 {
+            return None;
+        }
+        normalize(&mut sum);
+        Some(sum)
+    }
+}
+
+/// Cosine similarity of two unit vectors.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// PR embeddings keyed by PR key, persisted next to the Tantivy index.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VectorStore {
+    /// The `EmbeddingModel::id` the vectors were produced with.
+    model: String,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl VectorStore {
+    /// Load the stored vectors, discarding them if they came from another model.
+    pub fn load(cache_dir: &Path, model: &EmbeddingModel) -> Result<Self> {
+        let path = cache_dir.join(VECTOR_STORE_FILE);
+        let store = if path.exists() {
+            let contents = std::fs::read_to_string(path)?;
+            serde_json::from_str(&contents)?
+        } else {
+            Self::default()
+        };
+
+        if store.model == model.id() {
+            Ok(store)
+        } else {
+            Ok(Self {
+                model: model.id(),
+                vectors: HashMap::new(),
+            })
+        }
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        let contents = serde_json::to_string(self)?;
+        std::fs::write(cache_dir.join(VECTOR_STORE_FILE), contents)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[f32]> {
+        self.vectors.get(key).map(Vec::as_slice)
+    }
+
+    pub fn insert(&mut self, key: String, vector: Vec<f32>) {
+        self.vectors.insert(key, vector);
+    }
+
+    /// Drop every vector belonging to `repo`.
+    pub fn remove_repo(&mut self, repo: &str) {
+        let prefix = format!("{}#", repo);
+        self.vectors.retain(|key, _| !key.starts_with(&prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<EmbeddingModel> {
+        let path = std::env::temp_dir().join(format!("pr-similarity-{}-{}.vec", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let model = EmbeddingModel::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        model
+    }
+
+    /// The error from loading `contents`; models are too big to debug-print.
+    fn load_error(name: &str, contents: &str) -> String {
+        match load(name, contents) {
+            Ok(_) => panic!("{} loaded", name),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn loads_vectors_without_a_header() {
+        let model = load("no-header", "retry 1 0 0\nProvider 0 2 0\n\n").unwrap();
+
+        assert_eq!(model.dim, 3);
+        assert_eq!(model.vectors.len(), 2);
+        assert_eq!(model.embed("retry"), Some(vec![1.0, 0.0, 0.0]));
+        assert_eq!(model.embed("PROVIDER"), Some(vec![0.0, 1.0, 0.0]));
+        assert_eq!(model.embed("unknown words"), None);
+    }
+
+    #[test]
+    fn skips_the_count_dim_header() {
+        let model = load("header", "2 3\nretry 1 0 0\nprovider 0 1 0\n").unwrap();
+
+        assert_eq!(model.dim, 3);
+        assert_eq!(model.vectors.len(), 2);
+        assert!(!model.vectors.contains_key("2"));
+        assert!(model.id().ends_with(".vec:3"), "{}", model.id());
+    }
+
+    #[test]
+    fn rejects_rows_of_another_dimension() {
+        let err = load_error("ragged", "retry 1 0 0\nprovider 0 1\n");
+        assert!(err.ends_with(":2: expected 3 dimensions, found 2"), "{}", err);
+
+        // A header only counts on the first line
+        let err = load_error("late-header", "retry 1 0\n2 3\n");
+        assert!(err.ends_with(":2: expected 2 dimensions, found 1"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_files_without_vectors() {
+        for (name, contents) in [("empty", ""), ("blank", "\n\n"), ("header-only", "0 300\n")] {
+            let err = load_error(name, contents);
+            assert!(err.ends_with("has no vectors"), "{}: {}", name, err);
+        }
+    }
+
+    #[test]
+    fn rejects_unparseable_components() {
+        let err = load_error("garbage", "retry 1 0\nprovider 0 one\n");
+        assert!(err.ends_with(":2: invalid vector component"), "{}", err);
+        assert!(EmbeddingModel::load(Path::new("/nonexistent/model.vec")).is_err());
+    }
+
+    #[test]
+    fn embeds_text_as_the_normalized_mean() {
+        let model = load("mean", "retry 1 0\nprovider 0 1\n").unwrap();
+        let vector = model.embed("Retry the provider").unwrap();
+
+        assert!((vector[0] - vector[1]).abs() < 1e-6);
+        assert!((cosine(&vector, &vector) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::RepoFilter;
+
+/// Values `checks_status` can take in the index.
+pub const CHECKS_STATUSES: [&str; 4] = ["all_passed", "some_failed", "incomplete", "none"];
+
+/// Values a PR's `status` can take in the index.
+pub const PR_STATES: [&str; 3] = ["merged", "closed", "open"];
+
+/// Structured conditions a search must satisfy on top of its text query.
+/// Unset conditions match everything.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub repos: RepoFilter,
+    pub merged_after: Option<DateTime<Utc>>,
+    /// Exclusive, unlike `merged_after`; see `parse_end_date`.
+    pub merged_before: Option<DateTime<Utc>>,
+    /// GitHub login of the PR author.
+    pub author: Option<String>,
+    /// PRs must carry every one of these labels.
+    pub labels: Vec<String>,
+    pub base_branch: Option<String>,
+    pub checks_status: Option<String>,
+    /// Only PRs that changed this file or something under this directory.
+    pub path_prefix: Option<String>,
+    /// PRs in any of these states (see `PR_STATES`); empty for all.
+    pub states: Vec<String>,
+}
+
+impl SearchFilters {
+    /// Split a search in two: merged PRs, and a separate ranking of
+    /// closed-unmerged and open PRs so that they don't crowd out the PRs
+    /// that actually landed. Filters that pick particular `states` are kept
+    /// as they are, with no second ranking.
+    pub fn merged_and_unmerged(self) -> (SearchFilters, Option<SearchFilters>) {
+        if !self.states.is_empty() {
+            return (self, None);
+        }
+        let unmerged = SearchFilters {
+            states: vec!["closed".to_string(), "open".to_string()],
+            ..self.clone()
+        };
+        let merged = SearchFilters {
+            states: vec!["merged".to_string()],
+            ..self
+        };
+        (merged, Some(unmerged))
+    }
+}
+
+impl From<RepoFilter> for SearchFilters {
+    fn from(repos: RepoFilter) -> Self {
+        Self {
+            repos,
+            ..Self::default()
+        }
+    }
+}
+
+/// Parse a date given on the command line: `2024-06-01`, an RFC 3339
+/// timestamp, or an age such as `90d` or `12w` counted back from now.
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc());
+    }
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    let invalid = || anyhow!("expected YYYY-MM-DD, an RFC 3339 timestamp or an age like 90d, got '{}'", s);
+    let age = if let Some(days) = s.strip_suffix('d') {
+        days.parse().ok().and_then(TimeDelta::try_days)
+    } else if let Some(weeks) = s.strip_suffix('w') {
+        weeks.parse().ok().and_then(TimeDelta::try_weeks)
+    } else {
+        return Err(invalid());
+    };
+    age.and_then(|age| Utc::now().checked_sub_signed(age)).ok_or_else(invalid)
+}
+
+/// Parse the end of a date range, for use as an exclusive bound. A bare
+/// `YYYY-MM-DD` means the end of that day, so `--merged-before 2024-05-01`
+/// still includes PRs merged on May 1st; other forms are as `parse_date`.
+pub fn parse_end_date(s: &str) -> Result<DateTime<Utc>> {
+    let end = parse_date(s)?;
+    match NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+        Ok(_) => Ok(end + TimeDelta::days(1)),
+        Err(_) => Ok(end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_date_accepts_dates_and_timestamps() {
+        assert_eq!(parse_date(" 2024-06-01 ").unwrap(), Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            parse_date("2024-06-01T12:30:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 10, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_date_counts_ages_back_from_now() {
+        let before = Utc::now();
+        let days = parse_date("90d").unwrap();
+        let weeks = parse_date("2w").unwrap();
+        let after = Utc::now();
+
+        assert!(before - TimeDelta::days(90) <= days && days <= after - TimeDelta::days(90));
+        assert!(before - TimeDelta::weeks(2) <= weeks && weeks <= after - TimeDelta::weeks(2));
+    }
+
+    #[test]
+    fn parse_date_rejects_bad_input() {
+        let inputs = ["", "d", "w", "9", "90x", "ninety d", "9日", "日", "2024-13-01"];
+        let overflowing = ["99999999999999d", "9999999999999999w", "99999999999999999999d"];
+        for input in inputs.into_iter().chain(overflowing) {
+            let error = parse_date(input).unwrap_err();
+            assert!(error.to_string().contains("expected YYYY-MM-DD"), "{}: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn parse_end_date_includes_the_whole_day() {
+        assert_eq!(parse_end_date("2024-05-01").unwrap(), Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 0).unwrap());
+        assert_eq!(
+            parse_end_date("2024-05-01T12:00:00Z").unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+        );
+        assert!(parse_end_date("9日").is_err());
+    }
+}
@@ -1,37 +1,99 @@
 use anyhow::{anyhow, Result};
-use octocrab::Octocrab;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use octocrab::models::issues::{Comment, Issue};
+use octocrab::models::pulls::PullRequest;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::PathBuf;
+
+use crate::api::{api_base_url, ApiClient, RetryPolicy, DEFAULT_API_URL};
+use crate::ci_failure::CiFailure;
+use crate::graphql::{pull_requests_query, RepositoryData};
+
+/// The GitHub App that reports Actions jobs as check runs.
+pub(crate) const ACTIONS_APP_SLUG: &str = "github-actions";
+
+/// REST requests one GraphQL batch makes at a time. Syncs run several
+/// batches at once, so this multiplies with `SyncOptions::concurrency`.
+const BATCH_REST_CONCURRENCY: usize = 2;
 
 pub struct GitHubClient {
-    client: Octocrab,
+    api: ApiClient,
     owner: String,
     repo: String,
 }
 
 #[derive(Debug)]
 pub struct PullRequestDetails {
+    pub repo: String,
     pub number: u64,
     pub title: String,
+    pub url: String,
     pub description: String,
+    pub author: String,
+    pub labels: Vec<String>,
+    /// Branch the PR was opened against.
+    pub base_branch: String,
+    pub merged_at: Option<DateTime<Utc>>,
     pub comments: Vec<String>,
+    pub review_comments: Vec<ReviewComment>,
     pub state: String,
     pub mergeable: Option<bool>,
     pub merged: bool,
-    pub workflows: Vec<WorkflowRun>,
+    /// CI results for the head commit, from every source GitHub reports.
+    pub checks: Vec<Check>,
+    /// Earlier CI failures that later commits of the PR fixed; only
+    /// collected when a sync asks for them.
+    pub ci_failures: Vec<CiFailure>,
     pub commits: Vec<CommitInfo>,
     pub diff: String,
 }
 
-#[derive(Debug)]
-pub struct WorkflowRun {
+/// Where a check result came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckSource {
+    /// A GitHub Actions workflow run.
+    Actions,
+    /// A check run from another GitHub App, e.g. Buildkite or CircleCI.
+    CheckRun,
+    /// A legacy commit status.
+    Status,
+}
+
+/// One CI result for a commit. Workflow runs, check runs and commit
+/// statuses are all expressed in the check-run vocabulary: `status` is
+/// `queued`, `in_progress` or `completed`, and `conclusion` is set once
+/// completed (`success`, `failure`, `neutral`, `skipped`, `cancelled`,
+/// `timed_out`, `action_required`).
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub source: CheckSource,
     pub id: String,
     pub name: String,
     pub status: String,
     pub conclusion: Option<String>,
-    pub html_url: String,
+    /// Page with the check's results.
+    pub details_url: String,
+    /// Logs archive, for Actions runs.
     pub logs_url: Option<String>,
     pub head_sha: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        matches!(self.conclusion.as_deref(), Some("success" | "neutral" | "skipped"))
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(
+            self.conclusion.as_deref(),
+            Some("failure" | "timed_out" | "cancelled" | "action_required")
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -43,103 +105,223 @@ pub struct CommitInfo {
     pub url: String,
 }
 
+/// An inline review comment, attached to a line of the PR's diff.
 #[derive(Debug)]
+pub struct ReviewComment {
+    pub author: String,
+    pub body: String,
+    pub path: String,
+    /// The diff hunk the comment was made on, ending at the commented line.
+    pub diff_hunk: String,
+    pub line: Option<u64>,
+    pub url: String,
+}
+
+#[derive(Debug)]
+pub struct IssueDetails {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+    pub url: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct PullRequestSummary {
+    pub repo: String,
     pub number: u64,
     pub title: String,
     pub state: String,
     pub merged: bool,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 impl GitHubClient {
+    /// A client authenticated with `GITHUB_TOKEN`, talking to the API at
+    /// `GITHUB_API_URL` if set (as it is in GitHub Actions) or else to
+    /// api.github.com.
     pub fn new(owner: String, repo: String) -> Result<Self> {
         let token = env::var("GITHUB_TOKEN")
             .map_err(|_| anyhow!("GITHUB_TOKEN environment variable not set"))?;
+        let base_url = env::var("GITHUB_API_URL").map_or(DEFAULT_API_URL.to_string(), |url| api_base_url(&url));
 
-        let client = Octocrab::builder().personal_token(token).build()?;
+        Ok(Self::from_api(ApiClient::new(token, &base_url)?, owner, repo))
+    }
 
-        Ok(Self {
-            client,
-            owner,
-            repo,
-        })
+    pub fn from_api(api: ApiClient, owner: String, repo: String) -> Self {
+        Self { api, owner, repo }
+    }
+
+    /// Use another API root, such as a GitHub Enterprise Server host; see
+    /// `api_base_url` for the forms accepted.
+    pub fn with_api_url(mut self, url: &str) -> Self {
+        self.api = self.api.with_base_url(&api_base_url(url));
+        self
+    }
+
+    /// Revalidate responses cached under `dir` instead of refetching them.
+    pub fn with_response_cache(mut self, dir: PathBuf) -> Self {
+        self.api = self.api.with_response_cache(dir);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.api = self.api.with_retry_policy(retry);
+        self
+    }
+
+    /// `/repos/{owner}/{repo}/{path}`
+    fn route(&self, path: &str) -> String {
+        format!("/repos/{}/{}/{}", self.owner, self.repo, path)
     }
 
-    pub async fn list_recent_merged_prs(&self, limit: usize) -> Result<Vec<PullRequestSummary>> {
+    /// The `owner/repo` name this client is bound to.
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    /// List the most recently created PRs, newest first: merged PRs only,
+    /// or every PR, open and closed, with `include_unmerged`.
+    pub async fn list_recent_prs(&self, limit: usize, include_unmerged: bool) -> Result<Vec<PullRequestSummary>> {
         let mut all_prs = Vec::new();
-        let mut page = 1u32;
+
+        let mut page = self
+            .api
+            .get_page::<PullRequest>(
+                &self.route("pulls"),
+                &[
+                    ("state", if include_unmerged { "all" } else { "closed" }.to_string()),
+                    ("sort", "created".to_string()),
+                    ("direction", "desc".to_string()),
+                    ("per_page", "100".to_string()),
+                ],
+            )
+            .await?;
 
         while all_prs.len() < limit {
-            let prs = self
-                .client
-                .pulls(&self.owner, &self.repo)
-                .list()
-                .state(octocrab::params::State::Closed) // We want closed PRs
-                .sort(octocrab::params::pulls::Sort::Created)
-                .direction(octocrab::params::Direction::Descending)
-                .per_page(100)
-                .page(page)
-                .send()
-                .await?;
-
-            let items = prs.items;
-            if items.is_empty() {
-                break;
-            }
+            let items = std::mem::take(&mut page.items);
 
             for pr in items {
                 if all_prs.len() >= limit {
                     break;
                 }
 
-                // Only include merged PRs (those with a merged_at timestamp)
-                if pr.merged_at.is_some() {
-                    let state = match pr.state {
-                        Some(state) => match state {
-                            octocrab::models::IssueState::Open => "open",
-                            octocrab::models::IssueState::Closed => "closed",
-                            _ => "other",
-                        },
-                        None => "unknown",
-                    }
-                    .to_string();
-
-                    all_prs.push(PullRequestSummary {
-                        number: pr.number,
-                        title: pr.title.unwrap_or_default(),
-                        state,
-                        merged: true,
-                    });
+                // Unless asked otherwise, only include merged PRs (those with a merged_at timestamp)
+                if include_unmerged || pr.merged_at.is_some() {
+                    all_prs.push(self.summary(pr));
+                }
+            }
+
+            match page.next.take() {
+                Some(next) => page = self.api.get_page(&next, &[]).await?,
+                None => break,
+            }
+        }
+
+        Ok(all_prs)
+    }
+
+    /// List PRs merged strictly after `since`, newest first. With
+    /// `unmerged_since`, PRs without a merge that were updated after it are
+    /// listed too, so open PRs are picked up again as they change. It is a
+    /// watermark of its own because merges can be rare: keyed to `since`,
+    /// every open PR updated since the last merge would be fetched again.
+    ///
+    /// PRs are walked in order of last update; a PR can't have been
+    /// merged after it was last updated, so paging stops at the first PR
+    /// updated before both watermarks. There is no limit: the caller moves
+    /// its watermark to the newest merge, so every PR merged since must be
+    /// listed or the older ones would never be fetched.
+    pub async fn list_prs_since(
+        &self,
+        since: DateTime<Utc>,
+        unmerged_since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PullRequestSummary>> {
+        let mut all_prs = Vec::new();
+        let oldest = unmerged_since.map_or(since, |unmerged_since| unmerged_since.min(since));
+
+        let mut page = self
+            .api
+            .get_page::<PullRequest>(
+                &self.route("pulls"),
+                &[
+                    ("state", if unmerged_since.is_some() { "all" } else { "closed" }.to_string()),
+                    ("sort", "updated".to_string()),
+                    ("direction", "desc".to_string()),
+                    ("per_page", "100".to_string()),
+                ],
+            )
+            .await?;
+
+        'pages: loop {
+            let items = std::mem::take(&mut page.items);
+
+            for pr in items {
+                if pr.updated_at.is_some_and(|updated| updated <= oldest) {
+                    break 'pages;
+                }
+
+                let wanted = match (pr.merged_at, unmerged_since) {
+                    (Some(merged_at), _) => merged_at > since,
+                    (None, Some(unmerged_since)) => pr.updated_at.is_none_or(|updated| updated > unmerged_since),
+                    (None, None) => false,
+                };
+                if wanted {
+                    all_prs.push(self.summary(pr));
                 }
             }
 
-            page += 1;
+            match page.next.take() {
+                Some(next) => page = self.api.get_page(&next, &[]).await?,
+                None => break,
+            }
         }
 
         Ok(all_prs)
     }
 
+    fn summary(&self, pr: PullRequest) -> PullRequestSummary {
+        let state = match pr.state {
+            Some(state) => match state {
+                octocrab::models::IssueState::Open => "open",
+                octocrab::models::IssueState::Closed => "closed",
+                _ => "other",
+            },
+            None => "unknown",
+        }
+        .to_string();
+
+        PullRequestSummary {
+            repo: self.full_name(),
+            number: pr.number,
+            title: pr.title.unwrap_or_default(),
+            state,
+            merged: pr.merged_at.is_some(),
+            merged_at: pr.merged_at,
+            updated_at: pr.updated_at,
+        }
+    }
+
     pub async fn get_pull_request_details(&self, pr_number: u64) -> Result<PullRequestDetails> {
         // Get PR details
-        let pr = self
-            .client
-            .pulls(&self.owner, &self.repo)
-            .get(pr_number)
+        let pr: PullRequest = self
+            .api
+            .get_json(&self.route(&format!("pulls/{}", pr_number)), &[])
             .await?;
 
         // Get PR comments
-        let comments = self
-            .client
-            .issues(&self.owner, &self.repo)
-            .list_comments(pr_number)
-            .send()
+        let comments: Vec<Comment> = self
+            .api
+            .get_all(&self.route(&format!("issues/{}/comments", pr_number)), &[])
             .await?;
 
-        let comments = comments
-            .items
+        let mut comments: Vec<String> = comments
             .into_iter()
             .map(|comment| comment.body.unwrap_or_default())
             .collect();
+        // A review's summary reads like any other PR comment
+        comments.extend(self.get_pr_review_bodies(pr_number).await?);
 
         // Convert state to string
         let state = match pr.state {
@@ -155,37 +337,131 @@ impl GitHubClient {
         // Check if PR was merged
         let merged = pr.merged_at.is_some();
 
-        // Get workflow runs for the PR's commit
+        // Get CI results for the PR's head commit
         let head_sha = pr.head.sha;
-        let workflows = self.get_workflow_runs(&head_sha).await?;
+        let checks = self.get_checks(&head_sha).await?;
 
         // Get commits in the PR
         let commits = self.get_pr_commits(pr_number).await?;
 
+        // Get inline review comments
+        let review_comments = self.get_pr_review_comments(pr_number).await?;
+
         // Get PR diff
         let diff = self.get_pr_diff(pr_number).await?;
 
         Ok(PullRequestDetails {
+            repo: self.full_name(),
             number: pr.number,
             title: pr.title.unwrap_or_default(),
+            url: pr.html_url.map(|url| url.to_string()).unwrap_or_default(),
             description: pr.body.unwrap_or_default(),
+            author: pr.user.map(|user| user.login).unwrap_or_default(),
+            labels: pr
+                .labels
+                .unwrap_or_default()
+                .into_iter()
+                .map(|label| label.name)
+                .collect(),
+            base_branch: pr.base.ref_field,
+            merged_at: pr.merged_at,
             comments,
+            review_comments,
             state,
             mergeable: pr.mergeable,
             merged,
-            workflows,
+            checks,
+            ci_failures: Vec::new(),
             commits,
             diff,
         })
     }
 
-    async fn get_workflow_runs(&self, commit_sha: &str) -> Result<Vec<WorkflowRun>> {
-        #[derive(Deserialize)]
-        struct Response {
-            total_count: usize,
-            workflow_runs: Vec<Run>,
+    pub async fn get_issue(&self, issue_number: u64) -> Result<IssueDetails> {
+        let issue: Issue = self
+            .api
+            .get_json(&self.route(&format!("issues/{}", issue_number)), &[])
+            .await?;
+
+        Ok(IssueDetails {
+            number: issue.number,
+            title: issue.title,
+            body: issue.body.unwrap_or_default(),
+            labels: issue.labels.into_iter().map(|label| label.name).collect(),
+            url: issue.html_url.to_string(),
+        })
+    }
+
+    /// Fetch the details of several PRs with a single GraphQL query, where
+    /// the REST path needs six requests per PR. Diffs aren't available over
+    /// GraphQL and still come from REST, one request each. A PR with more
+    /// comments, reviews, commits or checks than the query fetches is
+    /// fetched over REST instead, which pages through all of them.
+    ///
+    /// Returns one result per requested number, so that a missing PR or a
+    /// failed diff only loses that PR.
+    pub async fn get_pull_request_details_batch(&self, numbers: &[u64]) -> Result<Vec<Result<PullRequestDetails>>> {
+        let query = pull_requests_query(numbers);
+        let variables = serde_json::json!({ "owner": self.owner, "name": self.repo });
+        let mut data: RepositoryData = self.api.graphql(&query, variables).await?;
+
+        let fetches = numbers.iter().enumerate().map(|(i, &number)| {
+            let node = data.repository.remove(&format!("pr{}", i)).flatten();
+            async move {
+                let node = node.ok_or_else(|| anyhow!("PR #{} not found", number))?;
+                if node.is_truncated() {
+                    // Too busy for one query; the REST path pages through everything
+                    return self.get_pull_request_details(number).await;
+                }
+                let mut details = node.into_details(self.full_name());
+                for check in details.checks.iter_mut().filter(|c| c.source == CheckSource::Actions) {
+                    check.logs_url = check.id.parse().ok().map(|id| self.run_logs_url(id, None));
+                }
+                details.diff = self.get_pr_diff(number).await?;
+                Ok(details)
+            }
+        });
+        Ok(stream::iter(fetches).buffered(BATCH_REST_CONCURRENCY).collect().await)
+    }
+
+    /// Logs archive of a workflow run; the latest attempt unless `attempt`
+    /// is given.
+    fn run_logs_url(&self, run_id: u64, attempt: Option<u64>) -> String {
+        let run = self.api.url(&self.route(&format!("actions/runs/{}", run_id)));
+        match attempt {
+            Some(attempt) => format!("{}/attempts/{}/logs", run, attempt),
+            None => format!("{}/logs", run),
         }
+    }
 
+    /// SHA of a PR's current head commit.
+    pub async fn get_head_sha(&self, pr_number: u64) -> Result<String> {
+        let pr: PullRequest = self
+            .api
+            .get_json(&self.route(&format!("pulls/{}", pr_number)), &[])
+            .await?;
+        Ok(pr.head.sha)
+    }
+
+    /// Download the logs archive of an Actions check.
+    pub async fn download_logs(&self, check: &Check) -> Result<Vec<u8>> {
+        let url = check
+            .logs_url
+            .as_ref()
+            .ok_or_else(|| anyhow!("{} has no downloadable logs", check.name))?;
+        self.api.get_bytes(url).await
+    }
+
+    /// Every CI result for a commit: Actions workflow runs, check runs from
+    /// other apps, and commit statuses.
+    pub async fn get_checks(&self, commit_sha: &str) -> Result<Vec<Check>> {
+        let mut checks = self.get_workflow_runs(commit_sha).await?;
+        checks.extend(self.get_check_runs(commit_sha).await?);
+        checks.extend(self.get_commit_statuses(commit_sha).await?);
+        Ok(checks)
+    }
+
+    async fn get_workflow_runs(&self, commit_sha: &str) -> Result<Vec<Check>> {
         #[derive(Deserialize)]
         struct Run {
             id: u64,
@@ -195,48 +471,124 @@ impl GitHubClient {
             conclusion: Option<String>,
             html_url: String,
             run_attempt: Option<u64>,
+            run_started_at: Option<DateTime<Utc>>,
+            updated_at: Option<DateTime<Utc>>,
         }
 
-        #[derive(serde::Serialize)]
-        struct Params<'a> {
-            head_sha: &'a str,
-        }
-
-        let params = Params {
-            head_sha: commit_sha,
-        };
-        let response: Response = self
-            .client
-            .get(
-                format!("/repos/{}/{}/actions/runs", self.owner, self.repo),
-                Some(&params),
+        let workflow_runs: Vec<Run> = self
+            .api
+            .get_all_in(
+                &self.route("actions/runs"),
+                &[("head_sha", commit_sha.to_string())],
+                "workflow_runs",
             )
             .await?;
 
         let mut runs = Vec::new();
-        for run in response.workflow_runs {
-            // Get logs URL if available
-            let logs_url = run.run_attempt.map(|attempt| {
-                format!(
-                    "https://api.github.com/repos/{}/{}/actions/runs/{}/attempts/{}/logs",
-                    self.owner, self.repo, run.id, attempt
-                )
-            });
+        for run in workflow_runs {
+            let logs_url = Some(self.run_logs_url(run.id, run.run_attempt));
 
-            runs.push(WorkflowRun {
+            let status = run.status.unwrap_or_else(|| "unknown".to_string());
+            runs.push(Check {
+                source: CheckSource::Actions,
                 id: run.id.to_string(),
                 name: run.name.unwrap_or_else(|| "Unknown".to_string()),
-                status: run.status.unwrap_or_else(|| "unknown".to_string()),
+                // A run's last update is its completion once it has finished
+                completed_at: if status == "completed" { run.updated_at } else { None },
+                status,
                 conclusion: run.conclusion,
-                html_url: run.html_url,
+                details_url: run.html_url,
                 logs_url,
                 head_sha: run.head_sha,
+                started_at: run.run_started_at,
             });
         }
 
         Ok(runs)
     }
 
+    /// Check runs reported by GitHub Apps other than Actions, whose jobs
+    /// are already covered by the workflow runs.
+    async fn get_check_runs(&self, commit_sha: &str) -> Result<Vec<Check>> {
+        #[derive(Deserialize)]
+        struct CheckRun {
+            id: u64,
+            name: String,
+            head_sha: String,
+            status: String,
+            conclusion: Option<String>,
+            details_url: Option<String>,
+            html_url: Option<String>,
+            started_at: Option<DateTime<Utc>>,
+            completed_at: Option<DateTime<Utc>>,
+            app: Option<App>,
+        }
+
+        #[derive(Deserialize)]
+        struct App {
+            slug: String,
+        }
+
+        let check_runs: Vec<CheckRun> = self
+            .api
+            .get_all_in(&self.route(&format!("commits/{}/check-runs", commit_sha)), &[], "check_runs")
+            .await?;
+
+        Ok(check_runs
+            .into_iter()
+            .filter(|run| run.app.as_ref().is_none_or(|app| app.slug != ACTIONS_APP_SLUG))
+            .map(|run| Check {
+                source: CheckSource::CheckRun,
+                id: run.id.to_string(),
+                name: run.name,
+                status: run.status,
+                conclusion: run.conclusion,
+                details_url: run.details_url.or(run.html_url).unwrap_or_default(),
+                logs_url: None,
+                head_sha: run.head_sha,
+                started_at: run.started_at,
+                completed_at: run.completed_at,
+            })
+            .collect())
+    }
+
+    /// The latest commit status for each context.
+    async fn get_commit_statuses(&self, commit_sha: &str) -> Result<Vec<Check>> {
+        #[derive(Deserialize)]
+        struct Status {
+            id: u64,
+            context: String,
+            state: String,
+            target_url: Option<String>,
+            created_at: Option<DateTime<Utc>>,
+            updated_at: Option<DateTime<Utc>>,
+        }
+
+        let statuses: Vec<Status> = self
+            .api
+            .get_all_in(&self.route(&format!("commits/{}/status", commit_sha)), &[], "statuses")
+            .await?;
+
+        Ok(statuses
+            .into_iter()
+            .map(|status| {
+                let (check_status, conclusion) = status_conclusion(&status.state);
+                Check {
+                    source: CheckSource::Status,
+                    id: status.id.to_string(),
+                    name: status.context,
+                    status: check_status.to_string(),
+                    conclusion: conclusion.map(String::from),
+                    details_url: status.target_url.unwrap_or_default(),
+                    logs_url: None,
+                    head_sha: commit_sha.to_string(),
+                    started_at: status.created_at,
+                    completed_at: conclusion.and(status.updated_at),
+                }
+            })
+            .collect())
+    }
+
     async fn get_pr_commits(&self, pr_number: u64) -> Result<Vec<CommitInfo>> {
         #[derive(Deserialize)]
         struct CommitResponse {
@@ -257,11 +609,8 @@ impl GitHubClient {
             email: Option<String>,
         }
 
-        let route = format!(
-            "/repos/{}/{}/pulls/{}/commits",
-            self.owner, self.repo, pr_number
-        );
-        let commits: Vec<CommitResponse> = self.client.get(&route, None::<&()>).await?;
+        let route = self.route(&format!("pulls/{}/commits", pr_number));
+        let commits: Vec<CommitResponse> = self.api.get_all(&route, &[]).await?;
 
         let mut commit_infos = Vec::new();
         for commit in commits {
@@ -277,25 +626,75 @@ impl GitHubClient {
         Ok(commit_infos)
     }
 
+    /// The summary bodies of a PR's reviews. Reviews that only approve or
+    /// carry inline comments have an empty body and are left out.
+    async fn get_pr_review_bodies(&self, pr_number: u64) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct ReviewResponse {
+            body: Option<String>,
+        }
+
+        let route = self.route(&format!("pulls/{}/reviews", pr_number));
+        let reviews: Vec<ReviewResponse> = self.api.get_all(&route, &[]).await?;
+
+        Ok(reviews
+            .into_iter()
+            .filter_map(|review| review.body)
+            .filter(|body| !body.trim().is_empty())
+            .collect())
+    }
+
+    async fn get_pr_review_comments(&self, pr_number: u64) -> Result<Vec<ReviewComment>> {
+        #[derive(Deserialize)]
+        struct CommentResponse {
+            user: Option<User>,
+            body: String,
+            path: String,
+            diff_hunk: String,
+            line: Option<u64>,
+            original_line: Option<u64>,
+            html_url: String,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            login: String,
+        }
+
+        let route = self.route(&format!("pulls/{}/comments", pr_number));
+        let comments: Vec<CommentResponse> = self.api.get_all(&route, &[]).await?;
+
+        Ok(comments
+            .into_iter()
+            .map(|comment| ReviewComment {
+                author: comment.user.map(|user| user.login).unwrap_or_default(),
+                body: comment.body,
+                path: comment.path,
+                diff_hunk: comment.diff_hunk,
+                // Comments on outdated diffs only have an original line
+                line: comment.line.or(comment.original_line),
+                url: comment.html_url,
+            })
+            .collect())
+    }
+
     async fn get_pr_diff(&self, pr_number: u64) -> Result<String> {
-        // Use reqwest directly to get the diff since octocrab doesn't support custom Accept headers
-        let url = format!(
-            "https://api.github.com/repos/{}/{}/pulls/{}",
-            self.owner, self.repo, pr_number
-        );
-
-        let token = env::var("GITHUB_TOKEN")?;
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&url)
-            .header("Accept", "application/vnd.github.v3.diff")
-            .header("Authorization", format!("token {}", token))
-            .header("User-Agent", "Goose-PR-Analyzer/1.0")
-            .send()
-            .await?
-            .text()
-            .await?;
+        self.api
+            .get_text(
+                &self.route(&format!("pulls/{}", pr_number)),
+                "application/vnd.github.v3.diff",
+            )
+            .await
+    }
+}
 
-        Ok(response)
+/// Map a commit status state (`success`, `failure`, `error`, `pending`) to
+/// a check-run status and conclusion. Case-insensitive, since GraphQL
+/// reports states in upper case.
+pub(crate) fn status_conclusion(state: &str) -> (&'static str, Option<&'static str>) {
+    match state.to_lowercase().as_str() {
+        "success" => ("completed", Some("success")),
+        "failure" | "error" => ("completed", Some("failure")),
+        _ => ("in_progress", None),
     }
 }
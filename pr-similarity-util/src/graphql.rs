@@ -0,0 +1,422 @@
+//! Batched PR queries against the GitHub GraphQL API.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::github::{status_conclusion, Check, ACTIONS_APP_SLUG, CheckSource, CommitInfo, PullRequestDetails, ReviewComment};
+
+/// Everything `PullRequestDetails` needs except the diff, which GraphQL
+/// doesn't expose. Each connection reports whether it has more pages than
+/// were fetched; see `PullRequestNode::is_truncated`.
+const PR_FIELDS: &str = r#"
+fragment PrFields on PullRequest {
+  number
+  title
+  body
+  url
+  state
+  merged
+  mergedAt
+  mergeable
+  baseRefName
+  headRefOid
+  author { login }
+  labels(first: 50) { nodes { name } pageInfo { hasNextPage } }
+  comments(first: 100) { nodes { body } pageInfo { hasNextPage } }
+  reviews(first: 50) {
+    nodes {
+      body
+      comments(first: 50) {
+        nodes { body path diffHunk line originalLine url author { login } }
+        pageInfo { hasNextPage }
+      }
+    }
+    pageInfo { hasNextPage }
+  }
+  commits(first: 250) {
+    nodes { commit { oid message url author { name email } } }
+    pageInfo { hasNextPage }
+  }
+  headCommit: commits(last: 1) {
+    nodes {
+      commit {
+        statusCheckRollup {
+          contexts(first: 100) {
+            nodes {
+              __typename
+              ... on CheckRun {
+                databaseId name status conclusion detailsUrl startedAt completedAt
+                checkSuite {
+                  status conclusion
+                  app { slug }
+                  workflowRun { databaseId url workflow { name } }
+                }
+              }
+              ... on StatusContext { context state targetUrl createdAt }
+            }
+            pageInfo { hasNextPage }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// A query fetching the given PRs of one repository, aliased `pr0`, `pr1`, ...
+pub(crate) fn pull_requests_query(numbers: &[u64]) -> String {
+    let aliases: String = numbers
+        .iter()
+        .enumerate()
+        .map(|(i, number)| format!("    pr{}: pullRequest(number: {}) {{ ...PrFields }}\n", i, number))
+        .collect();
+    format!(
+        "query($owner: String!, $name: String!) {{\n  repository(owner: $owner, name: $name) {{\n{}  }}\n}}\n{}",
+        aliases, PR_FIELDS
+    )
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RepositoryData {
+    /// `pr0`, `pr1`, ... -> the PR, or null if it doesn't exist.
+    pub repository: HashMap<String, Option<PullRequestNode>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PullRequestNode {
+    number: u64,
+    title: String,
+    body: String,
+    url: String,
+    state: String,
+    merged: bool,
+    merged_at: Option<DateTime<Utc>>,
+    mergeable: String,
+    base_ref_name: String,
+    head_ref_oid: String,
+    author: Option<Actor>,
+    labels: Connection<Label>,
+    comments: Connection<Comment>,
+    reviews: Connection<Review>,
+    commits: Connection<CommitNode>,
+    head_commit: Connection<HeadCommitNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    nodes: Vec<T>,
+    /// Absent for `headCommit`, which only ever asks for the last commit.
+    #[serde(default)]
+    page_info: Option<PageInfo>,
+}
+
+impl<T> Connection<T> {
+    fn has_next_page(&self) -> bool {
+        self.page_info.as_ref().is_some_and(|info| info.has_next_page)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+}
+
+#[derive(Deserialize)]
+struct Actor {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Comment {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct Review {
+    body: String,
+    comments: Connection<ReviewCommentNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewCommentNode {
+    body: String,
+    path: String,
+    diff_hunk: String,
+    line: Option<u64>,
+    original_line: Option<u64>,
+    url: String,
+    author: Option<Actor>,
+}
+
+#[derive(Deserialize)]
+struct CommitNode {
+    commit: Commit,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+    oid: String,
+    message: String,
+    url: String,
+    author: Option<GitActor>,
+}
+
+#[derive(Deserialize)]
+struct HeadCommitNode {
+    commit: HeadCommit,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeadCommit {
+    status_check_rollup: Option<StatusCheckRollup>,
+}
+
+#[derive(Deserialize)]
+struct GitActor {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatusCheckRollup {
+    contexts: Connection<CheckContext>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "__typename")]
+enum CheckContext {
+    #[serde(rename_all = "camelCase")]
+    CheckRun {
+        database_id: Option<u64>,
+        name: String,
+        status: String,
+        conclusion: Option<String>,
+        details_url: Option<String>,
+        started_at: Option<DateTime<Utc>>,
+        completed_at: Option<DateTime<Utc>>,
+        check_suite: Option<CheckSuite>,
+    },
+    #[serde(rename_all = "camelCase")]
+    StatusContext {
+        context: String,
+        state: String,
+        target_url: Option<String>,
+        created_at: Option<DateTime<Utc>>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckSuite {
+    status: String,
+    conclusion: Option<String>,
+    app: Option<App>,
+    workflow_run: Option<WorkflowRunNode>,
+}
+
+#[derive(Deserialize)]
+struct App {
+    slug: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkflowRunNode {
+    database_id: u64,
+    url: String,
+    workflow: Workflow,
+}
+
+#[derive(Deserialize)]
+struct Workflow {
+    name: String,
+}
+
+impl PullRequestNode {
+    /// Whether any list on the PR was cut off at the query's page size, in
+    /// which case it has to be fetched some other way.
+    pub(crate) fn is_truncated(&self) -> bool {
+        let contexts_truncated = self
+            .head_commit
+            .nodes
+            .iter()
+            .filter_map(|node| node.commit.status_check_rollup.as_ref())
+            .any(|rollup| rollup.contexts.has_next_page());
+        self.labels.has_next_page()
+            || self.comments.has_next_page()
+            || self.reviews.has_next_page()
+            || self.reviews.nodes.iter().any(|review| review.comments.has_next_page())
+            || self.commits.has_next_page()
+            || contexts_truncated
+    }
+
+    /// Convert to `PullRequestDetails` with an empty diff.
+    pub(crate) fn into_details(self, repo: String) -> PullRequestDetails {
+        let head_sha = self.head_ref_oid;
+        let checks = self
+            .head_commit
+            .nodes
+            .into_iter()
+            .next()
+            .and_then(|node| node.commit.status_check_rollup)
+            .map(|rollup| checks(rollup.contexts.nodes, &head_sha))
+            .unwrap_or_default();
+
+        let mut comments: Vec<String> = self.comments.nodes.into_iter().map(|c| c.body).collect();
+        let mut review_comments = Vec::new();
+        for review in self.reviews.nodes {
+            // A review's summary reads like any other PR comment
+            if !review.body.trim().is_empty() {
+                comments.push(review.body);
+            }
+            review_comments.extend(review.comments.nodes.into_iter().map(|comment| ReviewComment {
+                author: comment.author.map(|a| a.login).unwrap_or_default(),
+                body: comment.body,
+                path: comment.path,
+                diff_hunk: comment.diff_hunk,
+                line: comment.line.or(comment.original_line),
+                url: comment.url,
+            }));
+        }
+
+        PullRequestDetails {
+            repo,
+            number: self.number,
+            title: self.title,
+            url: self.url,
+            description: self.body,
+            author: self.author.map(|a| a.login).unwrap_or_default(),
+            labels: self.labels.nodes.into_iter().map(|l| l.name).collect(),
+            base_branch: self.base_ref_name,
+            merged_at: self.merged_at,
+            comments,
+            review_comments,
+            state: match self.state.as_str() {
+                "OPEN" => "open",
+                "CLOSED" | "MERGED" => "closed",
+                _ => "other",
+            }
+            .to_string(),
+            mergeable: match self.mergeable.as_str() {
+                "MERGEABLE" => Some(true),
+                "CONFLICTING" => Some(false),
+                _ => None,
+            },
+            merged: self.merged,
+            checks,
+            ci_failures: Vec::new(),
+            commits: self
+                .commits
+                .nodes
+                .into_iter()
+                .map(|node| {
+                    let author = node.commit.author;
+                    CommitInfo {
+                        sha: node.commit.oid,
+                        message: node.commit.message,
+                        author: author.as_ref().and_then(|a| a.name.clone()).unwrap_or_default(),
+                        author_email: author.and_then(|a| a.email),
+                        url: node.commit.url,
+                    }
+                })
+                .collect(),
+            diff: String::new(),
+        }
+    }
+}
+
+/// Convert rollup contexts to the checks the REST path reports. Actions
+/// jobs appear in the rollup as individual check runs; they are folded into
+/// one check per workflow run, timed from the first job start to the last
+/// job completion.
+fn checks(contexts: Vec<CheckContext>, head_sha: &str) -> Vec<Check> {
+    let mut checks: Vec<Check> = Vec::new();
+    for context in contexts {
+        let check = match context {
+            CheckContext::CheckRun {
+                database_id,
+                name,
+                status,
+                conclusion,
+                details_url,
+                started_at,
+                completed_at,
+                check_suite,
+            } => match check_suite {
+                Some(CheckSuite {
+                    status: suite_status,
+                    conclusion: suite_conclusion,
+                    app: Some(App { slug }),
+                    workflow_run: Some(run),
+                }) if slug == ACTIONS_APP_SLUG => {
+                    let id = run.database_id.to_string();
+                    if let Some(existing) = checks
+                        .iter_mut()
+                        .find(|c| c.source == CheckSource::Actions && c.id == id)
+                    {
+                        existing.started_at = existing.started_at.into_iter().chain(started_at).min();
+                        existing.completed_at = existing.completed_at.into_iter().chain(completed_at).max();
+                        continue;
+                    }
+                    Check {
+                        source: CheckSource::Actions,
+                        id,
+                        name: run.workflow.name,
+                        status: suite_status.to_lowercase(),
+                        conclusion: suite_conclusion.map(|c| c.to_lowercase()),
+                        details_url: run.url,
+                        logs_url: None,
+                        head_sha: head_sha.to_string(),
+                        started_at,
+                        completed_at,
+                    }
+                }
+                _ => Check {
+                    source: CheckSource::CheckRun,
+                    id: database_id.map(|id| id.to_string()).unwrap_or_default(),
+                    name,
+                    status: status.to_lowercase(),
+                    conclusion: conclusion.map(|c| c.to_lowercase()),
+                    details_url: details_url.unwrap_or_default(),
+                    logs_url: None,
+                    head_sha: head_sha.to_string(),
+                    started_at,
+                    completed_at,
+                },
+            },
+            CheckContext::StatusContext {
+                context,
+                state,
+                target_url,
+                created_at,
+            } => {
+                let (status, conclusion) = status_conclusion(&state);
+                Check {
+                    source: CheckSource::Status,
+                    id: String::new(),
+                    name: context,
+                    status: status.to_string(),
+                    conclusion: conclusion.map(String::from),
+                    details_url: target_url.unwrap_or_default(),
+                    logs_url: None,
+                    head_sha: head_sha.to_string(),
+                    started_at: created_at,
+                    completed_at: None,
+                }
+            }
+        };
+        checks.push(check);
+    }
+    checks
+}
@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::github::PullRequestDetails;
+
+/// Parse a single `gh pr view` dump, as found in `sample_data/`.
+///
+/// A dump is a block of `key:\tvalue` header lines, a `--` separator, the
+/// PR body and, when `gh pr diff` output was appended, the unified diff.
+pub fn parse_pr_view(text: &str) -> Result<PullRequestDetails> {
+    let mut lines = text.lines();
+    let mut header = HashMap::new();
+
+    for line in lines.by_ref() {
+        if line == "--" {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            header.insert(key.trim(), value.trim());
+        }
+    }
+
+    let mut description = Vec::new();
+    let mut diff = Vec::new();
+    for line in lines {
+        if !diff.is_empty() || line.starts_with("diff --git ") {
+            diff.push(line);
+        } else {
+            description.push(line);
+        }
+    }
+
+    let field = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| anyhow!("missing '{}' header", key))
+    };
+
+    let number_text = field("number")?;
+    let number = number_text
+        .parse()
+        .map_err(|_| anyhow!("invalid PR number '{}'", number_text))?;
+    let url = field("url")?;
+    let repo = repo_from_url(url)?;
+
+    let gh_state = field("state")?;
+    let merged = gh_state.eq_ignore_ascii_case("merged");
+    let state = match gh_state.to_ascii_uppercase().as_str() {
+        "MERGED" | "CLOSED" => "closed",
+        "OPEN" | "DRAFT" => "open",
+        _ => "unknown",
+    }
+    .to_string();
+
+    let mut diff = diff.join("\n");
+    if !diff.is_empty() {
+        diff.push('\n');
+    }
+
+    Ok(PullRequestDetails {
+        repo,
+        number,
+        title: field("title")?.to_string(),
+        url: url.to_string(),
+        description: description.join("\n").trim().to_string(),
+        author: field("author")?.to_string(),
+        labels: field("labels")?
+            .split(',')
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+            .map(String::from)
+            .collect(),
+        // Not part of a `gh pr view` dump
+        base_branch: String::new(),
+        merged_at: None,
+        comments: Vec::new(),
+        review_comments: Vec::new(),
+        state,
+        mergeable: None,
+        merged,
+        checks: Vec::new(),
+        ci_failures: Vec::new(),
+        commits: Vec::new(),
+        diff,
+    })
+}
+
+/// Read every dump under `path`, which may be a single file or a directory
+/// searched recursively for `.txt` files.
+pub fn read_pr_dumps(path: &Path) -> Result<Vec<PullRequestDetails>> {
+    let mut files = Vec::new();
+    collect_dump_files(path, &mut files)?;
+    files.sort();
+
+    files
+        .iter()
+        .map(|file| {
+            let text = std::fs::read_to_string(file)?;
+            parse_pr_view(&text).with_context(|| format!("failed to parse {}", file.display()))
+        })
+        .collect()
+}
+
+fn collect_dump_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_dump_files(&entry?.path(), files)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "txt") {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// `https://github.com/block/goose/pull/804` -> `block/goose`
+fn repo_from_url(url: &str) -> Result<String> {
+    let path = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split('/')
+        .collect::<Vec<_>>();
+    match path.as_slice() {
+        [_host, owner, repo, "pull", ..] => Ok(format!("{}/{}", owner, repo)),
+        _ => Err(anyhow!("unrecognized PR url '{}'", url)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `gh pr view` dump with a `gh pr diff` appended.
+    const PR_813: &str = include_str!("../sample_data/closed/pr_813.txt");
+
+    fn dump(state: &str, labels: &str) -> String {
+        format!(
+            "title:\tfeat: selective render\nstate:\t{}\nauthor:\tlaanak08\nlabels:\t{}\n\
+             number:\t630\nurl:\thttps://github.com/block/goose/pull/630\n--\nBody text.\n",
+            state, labels
+        )
+    }
+
+    #[test]
+    fn parses_sample_dump() {
+        let pr = parse_pr_view(PR_813).unwrap();
+        assert_eq!(pr.repo, "block/goose");
+        assert_eq!(pr.number, 813);
+        assert_eq!(pr.title, "docs: change youtube link");
+        assert_eq!(pr.url, "https://github.com/block/goose/pull/813");
+        assert_eq!(pr.author, "angiejones");
+        assert!(pr.labels.is_empty());
+        assert!(pr.merged);
+        assert_eq!(pr.state, "closed");
+    }
+
+    #[test]
+    fn separates_body_from_appended_diff() {
+        let pr = parse_pr_view(PR_813).unwrap();
+        assert!(pr.description.starts_with("new yt, who dis"));
+        assert!(pr.description.ends_with("https://app.asana.com/0/0/1209244627242076"));
+        assert!(pr.diff.starts_with("diff --git a/documentation/docusaurus.config.ts"));
+        assert!(pr.diff.ends_with('\n'));
+        assert_eq!(crate::extract_files_from_diff(&pr.diff), ["documentation/docusaurus.config.ts"]);
+    }
+
+    #[test]
+    fn maps_states_and_labels() {
+        let open = parse_pr_view(&dump("OPEN", "dependencies, rust ")).unwrap();
+        assert_eq!((open.state.as_str(), open.merged), ("open", false));
+        assert_eq!(open.labels, ["dependencies", "rust"]);
+        assert_eq!(open.description, "Body text.");
+        assert!(open.diff.is_empty());
+
+        let draft = parse_pr_view(&dump("DRAFT", "")).unwrap();
+        assert_eq!(draft.state, "open");
+        let closed = parse_pr_view(&dump("CLOSED", "")).unwrap();
+        assert_eq!((closed.state.as_str(), closed.merged), ("closed", false));
+    }
+
+    #[test]
+    fn rejects_incomplete_dumps() {
+        let without_url = dump("OPEN", "").replace("url:\thttps://github.com/block/goose/pull/630\n", "");
+        let error = parse_pr_view(&without_url).unwrap_err();
+        assert_eq!(error.to_string(), "missing 'url' header");
+
+        let bad_number = dump("OPEN", "").replace("number:\t630", "number:\tsix");
+        assert!(parse_pr_view(&bad_number).is_err());
+    }
+
+    #[test]
+    fn reads_every_sample_dump() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_data");
+        let prs = read_pr_dumps(&dir).unwrap();
+        assert_eq!(prs.len(), 40);
+        assert!(prs.iter().all(|pr| pr.repo == "block/goose" && !pr.title.is_empty()));
+    }
+
+    #[test]
+    fn repo_from_pr_urls() {
+        assert_eq!(repo_from_url("https://github.com/block/goose/pull/804").unwrap(), "block/goose");
+        assert_eq!(repo_from_url("https://ghe.example.com/acme/widgets/pull/7/files").unwrap(), "acme/widgets");
+        assert!(repo_from_url("https://github.com/block/goose/issues/804").is_err());
+    }
+}
@@ -1,199 +1,1625 @@
 use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
 use tantivy::{
-    schema::{Schema, STORED, TEXT},
+    schema::{
+        Facet, FacetOptions, Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED,
+        STORED, STRING, TEXT,
+    },
     Index, IndexWriter,
-    collector::TopDocs,
-    query::QueryParser,
-    Document,
+    collector::{DocSetCollector, TopDocs},
+    query::{
+        AllQuery, Bm25StatisticsProvider, BooleanQuery, ConstScoreQuery, EnableScoring, Explanation, Occur, Query,
+        QueryParser, RangeQuery, TermQuery,
+    },
+    DateTime, DocId, Document, DocAddress, Score, Searcher, SegmentReader, SnippetGenerator, TantivyError, Term,
     directory::MmapDirectory,
 };
+use std::ops::Bound;
+use std::path::PathBuf;
 
+pub mod api;
+pub mod ci_failure;
+pub mod cochange;
+pub mod config;
+pub mod diff;
+pub mod embedding;
+pub mod filter;
 pub mod github;
-use github::{GitHubClient, PullRequestDetails, PullRequestSummary};
+mod graphql;
+pub mod import;
+pub mod output;
+pub mod overlap;
+pub mod query;
+pub mod sync_state;
+pub mod tokenizer;
+pub mod zip;
+use ci_failure::{failure_signature, failure_title, resolved_failures, CiFailure};
+use cochange::{CoChangeGraph, RelatedFile};
+use config::{SearchConfig, SEARCH_FIELDS};
+use diff::{parse_hunk_header, parse_hunks, DiffHunk};
+use embedding::{cosine, EmbeddingModel, VectorStore};
+use filter::SearchFilters;
+use overlap::{path_features, weighted_jaccard};
+use github::{Check, GitHubClient, PullRequestDetails, PullRequestSummary, ReviewComment};
+use sync_state::{SyncOptions, SyncState, MAX_FETCH_ATTEMPTS};
+use tokenizer::{code_analyzer, query_tokenizers, CODE_TOKENIZER};
 
 pub struct PRSearchIndex {
     index: Index,
     writer: IndexWriter,
     query_parser: QueryParser,
-    github: GitHubClient,
+    hunk_query_parser: QueryParser,
+    review_comment_query_parser: QueryParser,
+    config: SearchConfig,
+    clients: Vec<GitHubClient>,
+    cache_dir: PathBuf,
+    embedder: Option<EmbeddingModel>,
+    vectors: VectorStore,
+    sync_options: SyncOptions,
+}
+
+/// Which repositories a search should consider.
+#[derive(Debug, Clone, Default)]
+pub enum RepoFilter {
+    #[default]
+    All,
+    /// Only PRs from these `owner/repo` names.
+    Only(Vec<String>),
 }
 
 impl PRSearchIndex {
     pub fn new(owner: String, repo: String, cache_dir: &std::path::Path) -> Result<Self> {
+        let client = GitHubClient::new(owner, repo)?.with_response_cache(cache_dir.join("http"));
+        Self::with_config(vec![client], cache_dir, SearchConfig::default())
+    }
+
+    /// Open an index shared by several repositories. Each client is synced
+    /// by `load_recent_prs`; documents from all of them live side by side.
+    pub fn with_config(
+        clients: Vec<GitHubClient>,
+        cache_dir: &std::path::Path,
+        config: SearchConfig,
+    ) -> Result<Self> {
+        // Title, file paths and diffs are full of identifiers, so they get
+        // the code-aware tokenizer instead of the default one
+        let code_text = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(CODE_TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+
         // Create schema
         let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("pr_key", STRING);
+        schema_builder.add_text_field("doc_type", STRING);
+        schema_builder.add_text_field("repo", STRING | STORED);
         schema_builder.add_text_field("pr_number", TEXT | STORED);
-        schema_builder.add_text_field("title", TEXT | STORED);
-        schema_builder.add_text_field("description", TEXT);
-        schema_builder.add_text_field("status", STORED);
-        schema_builder.add_text_field("checks_status", STORED);
-        schema_builder.add_text_field("files", TEXT | STORED);
-        schema_builder.add_text_field("diff", TEXT);
+        schema_builder.add_text_field("title", code_text.clone() | STORED);
+        schema_builder.add_text_field("url", STORED);
+        schema_builder.add_text_field("description", TEXT | STORED);
+        schema_builder.add_text_field("status", STRING | STORED);
+        schema_builder.add_text_field("checks_status", STRING | STORED);
+        schema_builder.add_text_field("files", code_text.clone() | STORED);
+        schema_builder.add_text_field("diff", code_text.clone());
+        // The diff itself is too big to store whole; keep a capped copy for snippets
+        schema_builder.add_text_field("stored_diff", STORED);
+        schema_builder.add_u64_field("diff_bytes", STORED);
+        schema_builder.add_text_field("commits", TEXT | STORED);
+        // Issue comments and inline review comments, one value per comment
+        schema_builder.add_text_field("comments", TEXT | STORED);
+        schema_builder.add_text_field("review_comments", TEXT | STORED);
+        // Child documents get text fields of their own, so that they don't
+        // count towards the term statistics that PR documents are ranked by
+        schema_builder.add_text_field("hunk_path", code_text.clone() | STORED);
+        schema_builder.add_text_field("hunk_diff", code_text.clone());
+        schema_builder.add_u64_field("old_start", STORED);
+        schema_builder.add_u64_field("old_lines", STORED);
+        schema_builder.add_u64_field("new_start", STORED);
+        schema_builder.add_u64_field("new_lines", STORED);
+        schema_builder.add_text_field("hunk_header", STORED);
+        // Filterable PR metadata, copied onto hunk and review comment
+        // documents so filters apply to them too
+        schema_builder.add_text_field("author", STRING | STORED);
+        schema_builder.add_text_field("labels", STRING | STORED);
+        schema_builder.add_text_field("base_branch", STRING | STORED);
+        schema_builder.add_date_field("merged_at", INDEXED | STORED | FAST);
+        schema_builder.add_facet_field("paths", FacetOptions::default());
+        // Review comment documents keep their line in `new_start`
+        schema_builder.add_text_field("comment_path", code_text.clone() | STORED);
+        schema_builder.add_text_field("comment_diff_hunk", code_text.clone());
+        schema_builder.add_text_field("comment_body", TEXT | STORED);
+        // CI failure documents keep the check's URL in `url`
+        schema_builder.add_text_field("failure_pr_title", STORED);
+        schema_builder.add_text_field("failure_signature", code_text | STORED);
+        schema_builder.add_text_field("failure_check", STORED);
+        schema_builder.add_text_field("failed_commit", STORED);
+        schema_builder.add_text_field("fixing_commits", STORED);
         let schema = schema_builder.build();
 
         // Create/open index on disk
-        std::fs::create_dir_all(cache_dir)?;
-        let dir = MmapDirectory::open(cache_dir)?;
-        let index = Index::open_or_create(dir, schema)?;
+        let index = open_index(cache_dir, schema)?;
+        index.tokenizers().register(CODE_TOKENIZER, code_analyzer());
         let writer = index.writer(50_000_000)?; // 50MB buffer
 
-        let description_field = index.schema()
-            .get_field("description")
-            .expect("description field not found");
+        let schema = index.schema();
+        let search_fields: Vec<_> = SEARCH_FIELDS
+            .iter()
+            .map(|name| schema.get_field(name).expect("search field not found"))
+            .collect();
 
-        let query_parser = QueryParser::for_index(&index, vec![description_field]);
-        let github = GitHubClient::new(owner, repo)?;
+        let mut query_parser = QueryParser::new(schema.clone(), search_fields.clone(), query_tokenizers());
+        for (name, field) in SEARCH_FIELDS.iter().zip(&search_fields) {
+            query_parser.set_field_boost(*field, config.boost(name));
+        }
+        // Child fields are boosted like the PR fields they stand in for
+        let hunk_query_parser = child_query_parser(&schema, &config, &[("hunk_path", "files"), ("hunk_diff", "diff")]);
+        let review_comment_query_parser = child_query_parser(
+            &schema,
+            &config,
+            &[
+                ("comment_path", "files"),
+                ("comment_diff_hunk", "diff"),
+                ("comment_body", "review_comments"),
+            ],
+        );
+        let embedder = config
+            .embedding_model
+            .as_deref()
+            .map(EmbeddingModel::load)
+            .transpose()?;
+        let vectors = match &embedder {
+            Some(model) => VectorStore::load(cache_dir, model)?,
+            None => VectorStore::default(),
+        };
 
-        Ok(Self {
+        let mut search_index = Self {
             index,
             writer,
             query_parser,
-            github,
-        })
+            hunk_query_parser,
+            review_comment_query_parser,
+            config,
+            clients,
+            cache_dir: cache_dir.to_path_buf(),
+            embedder,
+            vectors,
+            sync_options: SyncOptions::default(),
+        };
+        search_index.backfill_vectors()?;
+        Ok(search_index)
+    }
+
+    /// Embed indexed PRs that have no vector for the current model, as
+    /// after reranking is first enabled or the model changes. Vectors are
+    /// otherwise only computed when a PR is indexed.
+    fn backfill_vectors(&mut self) -> Result<()> {
+        let Some(model) = &self.embedder else {
+            return Ok(());
+        };
+        let searcher = self.index.reader()?.searcher();
+        let text = |doc: &Document, name: &str| {
+            doc.get_first(self.field(name))
+                .and_then(|v| v.as_text())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let mut missing = Vec::new();
+        for doc in self.pr_documents(&searcher, &SearchFilters::default())? {
+            let Ok(number) = text(&doc, "pr_number").parse() else {
+                continue;
+            };
+            let key = pr_key(&text(&doc, "repo"), number);
+            if self.vectors.get(&key).is_none() {
+                if let Some(vector) = model.embed(&embedding_text(&text(&doc, "title"), &text(&doc, "description"))) {
+                    missing.push((key, vector));
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            eprintln!("Embedded {} indexed PRs with {}", missing.len(), model.id());
+            for (key, vector) in missing {
+                self.vectors.insert(key, vector);
+            }
+            self.vectors.save(&self.cache_dir)?;
+        }
+        Ok(())
     }
 
+    /// Bring the index up to date with every configured repository.
+    ///
+    /// For a repository with nothing indexed yet (or with `force_refresh`)
+    /// the most recent `limit` merged PRs are fetched. Otherwise every PR
+    /// merged since its stored watermark is fetched, however many there
+    /// are, and any PR already in the index is replaced.
+    /// `SyncOptions::include_unmerged` adds closed and open PRs that haven't
+    /// been merged, fetched again whenever they have been updated since the
+    /// last sync.
+    ///
+    /// PR details are fetched concurrently and the index is committed as
+    /// they arrive, per `SyncOptions`. A PR that can't be fetched is logged
+    /// and skipped; the watermark stays behind it so the next sync retries
+    /// it, until it has failed in `MAX_FETCH_ATTEMPTS` syncs.
     pub async fn load_recent_prs(&mut self, limit: usize, force_refresh: bool) -> Result<Vec<PullRequestSummary>> {
+        let mut state = SyncState::load(&self.cache_dir)?;
+        // The clients are borrowed while documents are written
+        let clients = std::mem::take(&mut self.clients);
+        let mut all_prs = Vec::new();
+        let mut result = Ok(());
+
+        for client in &clients {
+            match self.sync_repo(client, limit, force_refresh, &mut state).await {
+                Ok(prs) => all_prs.extend(prs),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.clients = clients;
+        result?;
+
+        self.commit()?;
+        state.save(&self.cache_dir)?;
+        Ok(all_prs)
+    }
+
+    /// Fetch and index new PRs from one repository, returning those indexed.
+    async fn sync_repo(
+        &mut self,
+        client: &GitHubClient,
+        limit: usize,
+        force_refresh: bool,
+        state: &mut SyncState,
+    ) -> Result<Vec<PullRequestSummary>> {
+        let repo = client.full_name();
+        let repo_term = Term::from_field_text(self.field("repo"), &repo);
+
         if force_refresh {
-            self.writer.delete_all_documents()?;
+            self.writer.delete_term(repo_term.clone());
+            self.vectors.remove_repo(&repo);
+            state.clear(&repo);
         }
 
-        // If we have documents and aren't forcing refresh, skip loading
-        let reader = self.index.reader()?;
-        if !force_refresh && reader.searcher().num_docs() > 0 {
-            return Ok(vec![]); // Return empty vec since we don't need the summaries
+        let options = self.sync_options.clone();
+        let has_docs = !force_refresh && self.index.reader()?.searcher().doc_freq(&repo_term)? > 0;
+        let prs = match state.watermark(&repo) {
+            Some(since) if has_docs => {
+                // Indexes synced before unmerged PRs had their own watermark
+                // start it at the merge watermark
+                let unmerged_since = options
+                    .include_unmerged
+                    .then(|| state.unmerged_watermark(&repo).unwrap_or(since));
+                client.list_prs_since(since, unmerged_since).await?
+            }
+            _ => client.list_recent_prs(limit, options.include_unmerged).await?,
+        };
+
+        let batch_size = options.graphql_batch_size;
+        let index_failures = options.index_failures;
+        let mut fetches = stream::iter(prs.chunks(batch_size.max(1)))
+            .map(|chunk| async move {
+                let mut details = if batch_size == 0 {
+                    vec![client.get_pull_request_details(chunk[0].number).await]
+                } else {
+                    let numbers: Vec<_> = chunk.iter().map(|pr| pr.number).collect();
+                    match client.get_pull_request_details_batch(&numbers).await {
+                        Ok(details) => details,
+                        // The whole batch failed; report it against each PR
+                        Err(e) => chunk.iter().map(|_| Err(anyhow::anyhow!("{:#}", e))).collect(),
+                    }
+                };
+                if index_failures {
+                    for pr in details.iter_mut().flatten() {
+                        pr.ci_failures = resolved_failures(client, pr).await;
+                    }
+                }
+                chunk.iter().zip(details).collect::<Vec<_>>()
+            })
+            .buffered(options.concurrency.max(1))
+            .flat_map(stream::iter);
+
+        let mut indexed = Vec::new();
+        // Each watermark stays behind the oldest PR that is still retried
+        let mut first_failure: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut first_unmerged_failure: Option<chrono::DateTime<chrono::Utc>> = None;
+        let mut given_up = Vec::new();
+        let mut done = 0;
+        let mut failed = 0;
+        while let Some((pr, details)) = fetches.next().await {
+            done += 1;
+            match details {
+                Ok(details) => {
+                    if details.merged || options.include_unmerged {
+                        self.upsert_pr(&details)?;
+                    }
+                    state.clear_failure(&repo, pr.number);
+                    indexed.push(pr.clone());
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("Skipping {}#{}: {:#}", repo, pr.number, e);
+                    let attempts = state.record_failure(&repo, pr.number);
+                    if attempts >= MAX_FETCH_ATTEMPTS {
+                        eprintln!("Giving up on {}#{} after {} failed syncs", repo, pr.number, attempts);
+                        given_up.push(pr);
+                    } else if let Some(merged_at) = pr.merged_at {
+                        first_failure = Some(first_failure.map_or(merged_at, |first| first.min(merged_at)));
+                    } else if let Some(updated_at) = pr.updated_at {
+                        first_unmerged_failure =
+                            Some(first_unmerged_failure.map_or(updated_at, |first| first.min(updated_at)));
+                    }
+                }
+            }
+
+            if done % options.commit_every.max(1) == 0 && done < prs.len() {
+                self.commit()?;
+                eprintln!("{}: {}/{} PRs fetched ({} failed)", repo, done, prs.len(), failed);
+            }
         }
+        eprintln!("{}: {}/{} PRs fetched ({} failed)", repo, done, prs.len(), failed);
 
-        let prs = self.github.list_recent_merged_prs(limit).await?;
-        
-        for pr in &prs {
-            let pr_details = self.github.get_pull_request_details(pr.number).await?;
-            if pr_details.merged {
-                self.index_pr(&pr_details)?;
+        for pr in indexed.iter().chain(given_up) {
+            match (pr.merged_at, pr.updated_at) {
+                (Some(merged_at), _) if first_failure.is_none_or(|first| merged_at < first) => {
+                    state.advance(&repo, merged_at);
+                }
+                (None, Some(updated_at))
+                    if options.include_unmerged && first_unmerged_failure.is_none_or(|first| updated_at < first) =>
+                {
+                    state.advance_unmerged(&repo, updated_at);
+                }
+                _ => {}
             }
         }
+        Ok(indexed)
+    }
+
+    pub fn set_sync_options(&mut self, options: SyncOptions) {
+        self.sync_options = options;
+    }
 
+    /// Commit indexed documents, along with their embeddings.
+    fn commit(&mut self) -> Result<()> {
         self.writer.commit()?;
-        Ok(prs)
+        if self.embedder.is_some() {
+            self.vectors.save(&self.cache_dir)?;
+        }
+        Ok(())
     }
 
-    fn index_pr(&mut self, pr: &PullRequestDetails) -> Result<()> {
+    /// Index PRs that were obtained without GitHub, e.g. parsed from
+    /// `gh pr view` dumps. Unmerged PRs are skipped unless the sync options
+    /// include them, as in a sync. Returns the number of PRs indexed.
+    pub fn import_prs(&mut self, prs: &[PullRequestDetails]) -> Result<usize> {
+        let mut imported = 0;
+        let include_unmerged = self.sync_options.include_unmerged;
+        for pr in prs.iter().filter(|pr| pr.merged || include_unmerged) {
+            self.upsert_pr(pr)?;
+            imported += 1;
+        }
+
+        self.commit()?;
+        Ok(imported)
+    }
+
+    /// Replace any existing documents for this PR with fresh ones.
+    fn upsert_pr(&mut self, pr: &PullRequestDetails) -> Result<()> {
+        let key = pr_key(&pr.repo, pr.number);
+        self.writer
+            .delete_term(Term::from_field_text(self.field("pr_key"), &key));
+        self.index_pr(pr)?;
+        if let Some(vector) = self.embed_pr(pr) {
+            self.vectors.insert(key, vector);
+        }
+        Ok(())
+    }
+
+    fn field(&self, name: &str) -> Field {
+        self.index
+            .schema()
+            .get_field(name)
+            .unwrap_or_else(|_| panic!("{} field not found", name))
+    }
+
+    fn embed_pr(&self, pr: &PullRequestDetails) -> Option<Vec<f32>> {
+        let model = self.embedder.as_ref()?;
+        model.embed(&embedding_text(&pr.title, &pr.description))
+    }
+
+    fn index_pr(&self, pr: &PullRequestDetails) -> Result<()> {
         let mut doc = Document::default();
         let schema = self.index.schema();
         
         // Get all the field accessors
+        let pr_key_field = schema.get_field("pr_key").expect("pr_key field not found");
+        let doc_type_field = schema.get_field("doc_type").expect("doc_type field not found");
+        let repo_field = schema.get_field("repo").expect("repo field not found");
         let pr_number_field = schema.get_field("pr_number").expect("pr_number field not found");
         let title_field = schema.get_field("title").expect("title field not found");
+        let url_field = schema.get_field("url").expect("url field not found");
         let description_field = schema.get_field("description").expect("description field not found");
-        let status_field = schema.get_field("status").expect("status field not found");
-        let checks_field = schema.get_field("checks_status").expect("checks_status field not found");
         let files_field = schema.get_field("files").expect("files field not found");
         let diff_field = schema.get_field("diff").expect("diff field not found");
+        let stored_diff_field = schema.get_field("stored_diff").expect("stored_diff field not found");
+        let commits_field = schema.get_field("commits").expect("commits field not found");
 
         // Add fields to document
+        doc.add_text(pr_key_field, pr_key(&pr.repo, pr.number));
+        doc.add_text(doc_type_field, DOC_TYPE_PR);
+        doc.add_text(repo_field, &pr.repo);
         doc.add_text(pr_number_field, pr.number.to_string());
         doc.add_text(title_field, &pr.title);
+        doc.add_text(url_field, &pr.url);
         doc.add_text(description_field, &pr.description);
-        doc.add_text(status_field, &pr.state);
-
-        // Aggregate checks status
-        let checks_status = if pr.workflows.iter().all(|w| w.conclusion.as_deref() == Some("success")) {
-            "all_passed"
-        } else if pr.workflows.iter().any(|w| w.conclusion.as_deref() == Some("failure")) {
-            "some_failed"
-        } else {
-            "incomplete"
-        };
-        doc.add_text(checks_field, checks_status);
 
         // Extract files from diff
         let files: Vec<_> = extract_files_from_diff(&pr.diff);
         doc.add_text(files_field, files.join("\n"));
+        self.add_filter_fields(&mut doc, pr, &files);
         
         // Add full diff for context
         doc.add_text(diff_field, &pr.diff);
+        doc.add_text(stored_diff_field, truncate_at_char_boundary(&pr.diff, MAX_STORED_DIFF_BYTES));
+
+        doc.add_u64(schema.get_field("diff_bytes").expect("diff_bytes field not found"), pr.diff.len() as u64);
+
+        // One value per commit so messages can be read back individually
+        for commit in &pr.commits {
+            doc.add_text(commits_field, &commit.message);
+        }
+
+        for comment in &pr.comments {
+            doc.add_text(self.field("comments"), comment);
+        }
+        for comment in &pr.review_comments {
+            doc.add_text(self.field("review_comments"), &comment.body);
+        }
+
+        self.writer.add_document(doc)?;
+
+        for hunk in parse_hunks(&pr.diff) {
+            self.index_hunk(pr, &hunk)?;
+        }
+        for comment in &pr.review_comments {
+            self.index_review_comment(pr, comment)?;
+        }
+        for failure in &pr.ci_failures {
+            self.index_ci_failure(pr, failure)?;
+        }
+        Ok(())
+    }
+
+    /// Index one diff hunk as a child document of its PR.
+    fn index_hunk(&self, pr: &PullRequestDetails, hunk: &DiffHunk) -> Result<()> {
+        let mut doc = Document::default();
+
+        doc.add_text(self.field("pr_key"), pr_key(&pr.repo, pr.number));
+        doc.add_text(self.field("doc_type"), DOC_TYPE_HUNK);
+        doc.add_text(self.field("repo"), &pr.repo);
+        doc.add_text(self.field("pr_number"), pr.number.to_string());
+        doc.add_text(self.field("hunk_path"), &hunk.path);
+        doc.add_text(self.field("hunk_diff"), &hunk.content);
+        doc.add_u64(self.field("old_start"), hunk.old_start);
+        doc.add_u64(self.field("old_lines"), hunk.old_lines);
+        doc.add_u64(self.field("new_start"), hunk.new_start);
+        doc.add_u64(self.field("new_lines"), hunk.new_lines);
+        doc.add_text(self.field("hunk_header"), &hunk.header);
+        self.add_filter_fields(&mut doc, pr, std::slice::from_ref(&hunk.path));
+
+        self.writer.add_document(doc)?;
+        Ok(())
+    }
+
+    /// Add the PR metadata that `SearchFilters` can select on, with `paths`
+    /// being the files the document covers.
+    fn add_filter_fields(&self, doc: &mut Document, pr: &PullRequestDetails, paths: &[String]) {
+        doc.add_text(self.field("status"), pr_status(pr));
+        doc.add_text(self.field("checks_status"), checks_status(pr));
+        doc.add_text(self.field("author"), &pr.author);
+        for label in &pr.labels {
+            doc.add_text(self.field("labels"), label);
+        }
+        doc.add_text(self.field("base_branch"), &pr.base_branch);
+        if let Some(merged_at) = pr.merged_at {
+            doc.add_date(
+                self.field("merged_at"),
+                DateTime::from_timestamp_secs(merged_at.timestamp()),
+            );
+        }
+        for path in paths {
+            doc.add_facet(self.field("paths"), path_facet(path));
+        }
+    }
+
+    /// Index one inline review comment as a child document of its PR, so
+    /// the discussion can be found by the code it was about.
+    fn index_review_comment(&self, pr: &PullRequestDetails, comment: &ReviewComment) -> Result<()> {
+        let mut doc = Document::default();
+
+        doc.add_text(self.field("pr_key"), pr_key(&pr.repo, pr.number));
+        doc.add_text(self.field("doc_type"), DOC_TYPE_REVIEW_COMMENT);
+        doc.add_text(self.field("repo"), &pr.repo);
+        doc.add_text(self.field("pr_number"), pr.number.to_string());
+        doc.add_text(self.field("url"), &comment.url);
+        doc.add_text(self.field("comment_path"), &comment.path);
+        doc.add_text(self.field("comment_diff_hunk"), &comment.diff_hunk);
+        doc.add_text(self.field("comment_body"), &comment.body);
+        if let Some(line) = comment.line {
+            doc.add_u64(self.field("new_start"), line);
+        }
+        self.add_filter_fields(&mut doc, pr, std::slice::from_ref(&comment.path));
+
+        self.writer.add_document(doc)?;
+        Ok(())
+    }
+
+    /// Index a CI failure that the PR went on to fix, keyed by its
+    /// normalized log excerpt.
+    fn index_ci_failure(&self, pr: &PullRequestDetails, failure: &CiFailure) -> Result<()> {
+        let mut doc = Document::default();
+
+        doc.add_text(self.field("pr_key"), pr_key(&pr.repo, pr.number));
+        doc.add_text(self.field("doc_type"), DOC_TYPE_CI_FAILURE);
+        doc.add_text(self.field("repo"), &pr.repo);
+        doc.add_text(self.field("pr_number"), pr.number.to_string());
+        doc.add_text(self.field("failure_pr_title"), &pr.title);
+        doc.add_text(self.field("url"), &failure.url);
+        doc.add_text(self.field("failure_signature"), failure_signature(&failure.excerpt.join("\n")));
+        doc.add_text(self.field("failure_check"), failure_title(failure));
+        doc.add_text(self.field("failed_commit"), &failure.commit_sha);
+        // Every commit after the failing one contributed to the fix
+        let fixing_commits = pr
+            .commits
+            .iter()
+            .skip_while(|commit| commit.sha != failure.commit_sha)
+            .skip(1);
+        for commit in fixing_commits {
+            let summary = commit.message.lines().next().unwrap_or_default();
+            doc.add_text(self.field("fixing_commits"), format!("{} {}", commit.sha, summary));
+        }
+        let paths: Vec<String> = failure
+            .location
+            .iter()
+            .map(|location| location.rsplit_once(':').map_or(location.as_str(), |(path, _)| path).to_string())
+            .collect();
+        self.add_filter_fields(&mut doc, pr, &paths);
 
         self.writer.add_document(doc)?;
         Ok(())
     }
 
     pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        self.search_in(query, limit, &SearchFilters::default())
+    }
+
+    /// Search, restricted to PRs that satisfy `filters`.
+    pub fn search_in(&self, query: &str, limit: usize, filters: &SearchFilters) -> Result<Vec<SearchResult>> {
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
 
-        let query = self.query_parser.parse_query(query)?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let query_text = query;
+        let (query, _) = self.query_parser.parse_query_lenient(query_text);
+        let query = self.filtered(query, DOC_TYPE_PR, filters);
+        let statistics = self.statistics(&searcher, DOC_TYPE_PR)?;
+        let candidates = if self.reranking() {
+            limit.max(self.config.rerank_candidates)
+        } else {
+            limit
+        };
+        let top_docs = match self.config.recency_half_life_days {
+            // Decay inside the collector so that recent PRs can make the
+            // cut that an undecayed top-k would have dropped them from
+            Some(half_life) => {
+                let now = chrono::Utc::now().timestamp();
+                let collector = TopDocs::with_limit(candidates).tweak_score(move |segment_reader: &SegmentReader| {
+                    let merged_at = segment_reader.fast_fields().date("merged_at").ok();
+                    move |doc: DocId, score: Score| {
+                        let timestamp = merged_at.as_ref().and_then(|column| column.first(doc));
+                        score * recency_factor(timestamp.map(DateTime::into_timestamp_secs), now, half_life)
+                    }
+                });
+                searcher.search_with_statistics_provider(&query, &collector, &statistics)?
+            }
+            None => searcher.search_with_statistics_provider(&query, &TopDocs::with_limit(candidates), &statistics)?,
+        };
+
+        let snippet_generators = SNIPPET_FIELDS
+            .iter()
+            .map(|&(field, stored_field)| {
+                let mut generator = SnippetGenerator::create(&searcher, &*query, self.field(field))?;
+                generator.set_max_num_chars(SNIPPET_MAX_CHARS);
+                Ok((field, self.field(stored_field), generator))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let field_queries = self.field_queries(query_text);
 
         let mut results = Vec::new();
+        for (score, doc_address) in top_docs {
+            let retrieved_doc = searcher.doc(doc_address)?;
+            let mut result = self.search_result(&retrieved_doc, score)?;
+            if let Some(half_life) = self.config.recency_half_life_days {
+                let factor = recency_factor(
+                    result.merged_at.map(|date| date.timestamp()),
+                    chrono::Utc::now().timestamp(),
+                    half_life,
+                );
+                result.recency_factor = Some(factor);
+                // The collector only saw the decayed score
+                result.bm25_score = explain(&*query, &statistics, doc_address)?.value();
+            }
+            result.field_scores = self.field_scores(&statistics, &field_queries, doc_address);
+
+            for (field, stored_field, generator) in &snippet_generators {
+                let text = retrieved_doc
+                    .get_first(*stored_field)
+                    .and_then(|v| v.as_text())
+                    .unwrap_or_default();
+                let snippet = generator.snippet(text);
+                if !snippet.is_empty() {
+                    result.snippets.push(SearchSnippet {
+                        field: field.to_string(),
+                        fragment: snippet.fragment().to_string(),
+                        highlighted: merge_ranges(snippet.highlighted().iter().map(|range| (range.start, range.end))),
+                    });
+                }
+            }
+            results.push(result);
+        }
+
+        if self.reranking() {
+            self.rerank(query_text, &mut results);
+            results.truncate(limit);
+        }
+
+        Ok(results)
+    }
+
+    /// Build a result from a stored PR document.
+    fn search_result(&self, retrieved_doc: &Document, score: f32) -> Result<SearchResult> {
         let schema = self.index.schema();
         
+        let repo_field = schema.get_field("repo").expect("repo field not found");
         let pr_number_field = schema.get_field("pr_number").expect("pr_number field not found");
         let title_field = schema.get_field("title").expect("title field not found");
+        let url_field = schema.get_field("url").expect("url field not found");
         let status_field = schema.get_field("status").expect("status field not found");
         let checks_field = schema.get_field("checks_status").expect("checks_status field not found");
         let files_field = schema.get_field("files").expect("files field not found");
 
-        for (score, doc_address) in top_docs {
-            let retrieved_doc = searcher.doc(doc_address)?;
-            
-            let pr_number = retrieved_doc
-                .get_first(pr_number_field)
-                .and_then(|v| v.as_text())
-                .ok_or_else(|| anyhow::anyhow!("pr_number not found"))?
-                .to_string();
-            
-            let title = retrieved_doc
-                .get_first(title_field)
-                .and_then(|v| v.as_text())
-                .ok_or_else(|| anyhow::anyhow!("title not found"))?
-                .to_string();
+        let repo = retrieved_doc
+            .get_first(repo_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("repo not found"))?
+            .to_string();
 
-            let status = retrieved_doc
-                .get_first(status_field)
-                .and_then(|v| v.as_text())
-                .ok_or_else(|| anyhow::anyhow!("status not found"))?
-                .to_string();
+        let pr_number = retrieved_doc
+            .get_first(pr_number_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("pr_number not found"))?
+            .to_string();
+        
+        let title = retrieved_doc
+            .get_first(title_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("title not found"))?
+            .to_string();
 
-            let checks_status = retrieved_doc
-                .get_first(checks_field)
-                .and_then(|v| v.as_text())
-                .ok_or_else(|| anyhow::anyhow!("checks_status not found"))?
-                .to_string();
+        let url = retrieved_doc
+            .get_first(url_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("url not found"))?
+            .to_string();
+
+        let status = retrieved_doc
+            .get_first(status_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("status not found"))?
+            .to_string();
+
+        let checks_status = retrieved_doc
+            .get_first(checks_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("checks_status not found"))?
+            .to_string();
+
+        let files = retrieved_doc
+            .get_first(files_field)
+            .and_then(|v| v.as_text())
+            .ok_or_else(|| anyhow::anyhow!("files not found"))?
+            .to_string();
 
-            let files = retrieved_doc
-                .get_first(files_field)
+        let merged_at = retrieved_doc
+            .get_first(schema.get_field("merged_at").expect("merged_at field not found"))
+            .and_then(|v| v.as_date())
+            .and_then(|date| chrono::DateTime::from_timestamp(date.into_timestamp_secs(), 0));
+
+        Ok(SearchResult {
+            repo,
+            pr_number: pr_number.parse()?,
+            title,
+            url,
+            status,
+            checks_status,
+            files: files
+                .split('\n')
+                .filter(|f| !f.is_empty())
+                .map(String::from)
+                .collect(),
+            merged_at,
+            score,
+            bm25_score: score,
+            semantic_score: None,
+            recency_factor: None,
+            field_scores: Vec::new(),
+            snippets: Vec::new(),
+            shared_files: Vec::new(),
+        })
+    }
+
+    /// Rank PRs by how much their changed files overlap `paths`, using
+    /// weighted Jaccard similarity over files and their parent directories.
+    /// No text query is involved.
+    pub fn search_by_files(&self, paths: &[String], limit: usize, filters: &SearchFilters) -> Result<Vec<SearchResult>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|path| path.trim().trim_start_matches("./").to_string())
+            .collect();
+        let wanted = path_features(&paths);
+
+        let mut results = Vec::new();
+        for retrieved_doc in self.pr_documents(&searcher, filters)? {
+            let mut result = self.search_result(&retrieved_doc, 0.0)?;
+
+            let score = weighted_jaccard(&wanted, &path_features(&result.files));
+            if score <= 0.0 {
+                continue;
+            }
+            result.score = score;
+            result.bm25_score = 0.0;
+            result.shared_files = result
+                .files
+                .iter()
+                .filter(|file| paths.contains(file))
+                .cloned()
+                .collect();
+            results.push(result);
+        }
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Build the file co-change graph from every indexed PR.
+    pub fn co_change_graph(&self, filters: &SearchFilters) -> Result<CoChangeGraph> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let files_field = self.field("files");
+
+        let file_lists = self
+            .pr_documents(&searcher, filters)?
+            .into_iter()
+            .map(|doc| {
+                doc.get_first(files_field)
+                    .and_then(|v| v.as_text())
+                    .unwrap_or_default()
+                    .split('\n')
+                    .map(String::from)
+                    .collect()
+            });
+        Ok(CoChangeGraph::build(file_lists))
+    }
+
+    /// Files most often changed in the same PRs as `path`.
+    pub fn related_files(&self, path: &str, limit: usize, filters: &SearchFilters) -> Result<Vec<RelatedFile>> {
+        let path = path.trim().trim_start_matches("./");
+        Ok(self.co_change_graph(filters)?.related(path, limit))
+    }
+
+    /// Every PR document that satisfies `filters`.
+    fn pr_documents(&self, searcher: &Searcher, filters: &SearchFilters) -> Result<Vec<Document>> {
+        let query = self.filtered(Box::new(AllQuery), DOC_TYPE_PR, filters);
+        let doc_addresses = searcher.search(&query, &DocSetCollector)?;
+        doc_addresses
+            .into_iter()
+            .map(|doc_address| Ok(searcher.doc(doc_address)?))
+            .collect()
+    }
+
+    fn reranking(&self) -> bool {
+        self.embedder.is_some() && self.config.semantic_weight > 0.0
+    }
+
+    /// Blend max-normalized BM25 scores with embedding similarity to the
+    /// query, apply any recency decay, and re-sort. PRs without a stored vector keep their lexical score.
+    fn rerank(&self, query: &str, results: &mut [SearchResult]) {
+        let Some(query_vector) = self.embedder.as_ref().and_then(|m| m.embed(query)) else {
+            return;
+        };
+        let max_bm25 = results.iter().map(|r| r.bm25_score).fold(0.0, f32::max);
+        let weight = self.config.semantic_weight;
+
+        for result in results.iter_mut() {
+            let lexical = if max_bm25 > 0.0 { result.bm25_score / max_bm25 } else { 0.0 };
+            let semantic = self
+                .vectors
+                .get(&pr_key(&result.repo, result.pr_number))
+                .map(|vector| cosine(&query_vector, vector));
+            result.semantic_score = semantic;
+            result.score = (1.0 - weight) * lexical + weight * semantic.unwrap_or(lexical);
+            result.score *= result.recency_factor.unwrap_or(1.0);
+        }
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+
+    /// Fetch a PR's stored document by number, from any repository.
+    pub fn get_pr(&self, pr_number: u64) -> Result<Option<StoredPr>> {
+        self.get_pr_in(pr_number, &RepoFilter::All)
+    }
+
+    /// Fetch a PR's stored document by number from the selected repositories.
+    /// It's an error for several of them to have a PR with this number.
+    pub fn get_pr_in(&self, pr_number: u64, repos: &RepoFilter) -> Result<Option<StoredPr>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        let query: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(self.field("pr_number"), &pr_number.to_string()),
+            IndexRecordOption::Basic,
+        ));
+        let query = self.filtered(query, DOC_TYPE_PR, &SearchFilters::from(repos.clone()));
+        let matches = searcher.search(&query, &DocSetCollector)?;
+        let mut docs = matches
+            .into_iter()
+            .map(|doc_address| searcher.doc(doc_address))
+            .collect::<Result<Vec<_>, _>>()?;
+        if docs.len() > 1 {
+            let mut repos: Vec<_> = docs
+                .iter()
+                .filter_map(|doc| doc.get_first(self.field("repo")).and_then(|v| v.as_text()))
+                .collect();
+            repos.sort_unstable();
+            return Err(anyhow::anyhow!(
+                "PR #{} is indexed for several repositories ({}); pick one with --repo",
+                pr_number,
+                repos.join(", ")
+            ));
+        }
+        let Some(doc) = docs.pop() else {
+            return Ok(None);
+        };
+
+        let summary = self.search_result(&doc, 0.0)?;
+        let text = |name: &str| {
+            doc.get_first(self.field(name))
                 .and_then(|v| v.as_text())
-                .ok_or_else(|| anyhow::anyhow!("files not found"))?
-                .to_string();
-
-            results.push(SearchResult {
-                pr_number: pr_number.parse()?,
-                title,
-                status,
-                checks_status,
-                files: files.split('\n').map(String::from).collect(),
+                .unwrap_or_default()
+                .to_string()
+        };
+        let diff = text("stored_diff");
+        let diff_bytes = doc
+            .get_first(self.field("diff_bytes"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default();
+
+        Ok(Some(StoredPr {
+            repo: summary.repo,
+            number: summary.pr_number,
+            title: summary.title,
+            url: summary.url,
+            status: summary.status,
+            checks_status: summary.checks_status,
+            description: text("description"),
+            files: summary.files,
+            commits: doc
+                .get_all(self.field("commits"))
+                .filter_map(|v| v.as_text())
+                .map(String::from)
+                .collect(),
+            comments: doc
+                .get_all(self.field("comments"))
+                .filter_map(|v| v.as_text())
+                .map(String::from)
+                .collect(),
+            review_comments: doc
+                .get_all(self.field("review_comments"))
+                .filter_map(|v| v.as_text())
+                .map(String::from)
+                .collect(),
+            diff_truncated: diff_bytes > diff.len() as u64,
+            diff,
+        }))
+    }
+
+    /// Search the individual diff hunks of indexed PRs, so matches point at
+    /// a file and line range rather than a whole PR.
+    pub fn search_hunks(&self, query: &str, limit: usize, filters: &SearchFilters) -> Result<Vec<HunkResult>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        let (query, _) = self.hunk_query_parser.parse_query_lenient(query);
+        let query = self.filtered(query, DOC_TYPE_HUNK, filters);
+        let statistics = self.statistics(&searcher, DOC_TYPE_HUNK)?;
+        let top_docs = searcher.search_with_statistics_provider(&query, &TopDocs::with_limit(limit), &statistics)?;
+
+        let mut results = Vec::new();
+        for (score, doc_address) in top_docs {
+            let doc = searcher.doc(doc_address)?;
+            let text = |name: &str| -> Result<String> {
+                doc.get_first(self.field(name))
+                    .and_then(|v| v.as_text())
+                    .map(String::from)
+                    .ok_or_else(|| anyhow::anyhow!("{} not found", name))
+            };
+            let number = |name: &str| -> Result<u64> {
+                doc.get_first(self.field(name))
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| anyhow::anyhow!("{} not found", name))
+            };
+
+            results.push(HunkResult {
+                repo: text("repo")?,
+                pr_number: text("pr_number")?.parse()?,
+                path: text("hunk_path")?,
+                old_start: number("old_start")?,
+                old_lines: number("old_lines")?,
+                new_start: number("new_start")?,
+                new_lines: number("new_lines")?,
+                header: text("hunk_header")?,
+                score,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Search inline review comments, matching their text, the file they
+    /// were made on and the diff hunk they quote.
+    pub fn search_review_comments(
+        &self,
+        query: &str,
+        limit: usize,
+        filters: &SearchFilters,
+    ) -> Result<Vec<ReviewCommentResult>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        let (query, _) = self.review_comment_query_parser.parse_query_lenient(query);
+        let query = self.filtered(query, DOC_TYPE_REVIEW_COMMENT, filters);
+        let statistics = self.statistics(&searcher, DOC_TYPE_REVIEW_COMMENT)?;
+        let top_docs = searcher.search_with_statistics_provider(&query, &TopDocs::with_limit(limit), &statistics)?;
+
+        let mut results = Vec::new();
+        for (score, doc_address) in top_docs {
+            let doc = searcher.doc(doc_address)?;
+            let text = |name: &str| -> Result<String> {
+                doc.get_first(self.field(name))
+                    .and_then(|v| v.as_text())
+                    .map(String::from)
+                    .ok_or_else(|| anyhow::anyhow!("{} not found", name))
+            };
+
+            results.push(ReviewCommentResult {
+                repo: text("repo")?,
+                pr_number: text("pr_number")?.parse()?,
+                path: text("comment_path")?,
+                line: doc.get_first(self.field("new_start")).and_then(|v| v.as_u64()),
+                body: text("comment_body")?,
+                url: text("url")?,
                 score,
             });
         }
 
         Ok(results)
     }
+
+    /// Find indexed CI failures that read like `excerpt`, a piece of a
+    /// failing log, along with the PRs and commits that fixed them.
+    pub fn similar_failures(&self, excerpt: &str, limit: usize, filters: &SearchFilters) -> Result<Vec<FailureMatch>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        // Only words go into the query, so log punctuation can't be read as
+        // query syntax; lowercase keeps `AND`/`OR`/`NOT` from being operators
+        let mut words: Vec<String> = Vec::new();
+        for word in failure_signature(excerpt)
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+        {
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let parser = QueryParser::new(
+            self.index.schema(),
+            vec![self.field("failure_signature")],
+            query_tokenizers(),
+        );
+        let (query, _) = parser.parse_query_lenient(&words.join(" "));
+        let query = self.filtered(query, DOC_TYPE_CI_FAILURE, filters);
+        let statistics = self.statistics(&searcher, DOC_TYPE_CI_FAILURE)?;
+        let top_docs = searcher.search_with_statistics_provider(&query, &TopDocs::with_limit(limit), &statistics)?;
+
+        let mut results = Vec::new();
+        for (score, doc_address) in top_docs {
+            let doc = searcher.doc(doc_address)?;
+            let text = |name: &str| -> Result<String> {
+                doc.get_first(self.field(name))
+                    .and_then(|v| v.as_text())
+                    .map(String::from)
+                    .ok_or_else(|| anyhow::anyhow!("{} not found", name))
+            };
+
+            results.push(FailureMatch {
+                repo: text("repo")?,
+                pr_number: text("pr_number")?.parse()?,
+                title: text("failure_pr_title")?,
+                check: text("failure_check")?,
+                failed_commit: text("failed_commit")?,
+                fixing_commits: doc
+                    .get_all(self.field("fixing_commits"))
+                    .filter_map(|v| v.as_text())
+                    .map(String::from)
+                    .collect(),
+                signature: text("failure_signature")?,
+                url: text("url")?,
+                score,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// BM25 statistics for ranking documents of `doc_type` among themselves.
+    fn statistics<'a>(&self, searcher: &'a Searcher, doc_type: &str) -> Result<DocTypeStatistics<'a>> {
+        let num_docs = searcher.doc_freq(&Term::from_field_text(self.field("doc_type"), doc_type))?;
+        Ok(DocTypeStatistics { searcher, num_docs })
+    }
+
+    /// Restrict a query to one document type and to documents that satisfy `filters`.
+    ///
+    /// Filter clauses only select documents; they score zero, so rankings
+    /// and reported scores come from `query` alone.
+    fn filtered(&self, query: Box<dyn Query>, doc_type: &str, filters: &SearchFilters) -> Box<dyn Query> {
+        let term_query = |name: &str, value: &str| -> Box<dyn Query> {
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.field(name), value),
+                IndexRecordOption::Basic,
+            ))
+        };
+        let filter = |query: Box<dyn Query>| -> (Occur, Box<dyn Query>) {
+            (Occur::Must, Box::new(ConstScoreQuery::new(query, 0.0)))
+        };
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+            (Occur::Must, query),
+            filter(term_query("doc_type", doc_type)),
+        ];
+
+        if let Some(author) = &filters.author {
+            clauses.push(filter(term_query("author", author)));
+        }
+        for label in &filters.labels {
+            clauses.push(filter(term_query("labels", label)));
+        }
+        if let Some(base_branch) = &filters.base_branch {
+            clauses.push(filter(term_query("base_branch", base_branch)));
+        }
+        if let Some(checks_status) = &filters.checks_status {
+            clauses.push(filter(term_query("checks_status", checks_status)));
+        }
+        if let Some(prefix) = &filters.path_prefix {
+            // Facets are indexed with all their ancestors, so a directory
+            // matches every file below it
+            clauses.push(filter(Box::new(TermQuery::new(
+                Term::from_facet(self.field("paths"), &path_facet(prefix)),
+                IndexRecordOption::Basic,
+            ))));
+        }
+        if filters.merged_after.is_some() || filters.merged_before.is_some() {
+            let date = |date: chrono::DateTime<chrono::Utc>| DateTime::from_timestamp_secs(date.timestamp());
+            clauses.push(filter(Box::new(RangeQuery::new_date_bounds(
+                "merged_at".to_string(),
+                filters.merged_after.map_or(Bound::Unbounded, |after| Bound::Included(date(after))),
+                filters.merged_before.map_or(Bound::Unbounded, |before| Bound::Excluded(date(before))),
+            ))));
+        }
+
+        if !filters.states.is_empty() {
+            let states = filters.states.iter().map(|state| term_query("status", state)).collect();
+            clauses.push(filter(Box::new(BooleanQuery::union(states))));
+        }
+
+        if let RepoFilter::Only(repos) = &filters.repos {
+            let repo_field = self.field("repo");
+            let repo_queries: Vec<Box<dyn Query>> = repos
+                .iter()
+                .map(|repo| -> Box<dyn Query> {
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(repo_field, repo),
+                        IndexRecordOption::Basic,
+                    ))
+                })
+                .collect();
+            clauses.push(filter(Box::new(BooleanQuery::union(repo_queries))));
+        }
+
+        Box::new(BooleanQuery::new(clauses))
+    }
+
+    /// Parse the query against each search field on its own, once per
+    /// search, for scoring matched documents field by field.
+    fn field_queries(&self, query: &str) -> Vec<(&'static str, Box<dyn Query>)> {
+        let schema = self.index.schema();
+        SEARCH_FIELDS
+            .iter()
+            .map(|&name| {
+                let field = schema.get_field(name).expect("search field not found");
+                let parser = QueryParser::new(schema.clone(), vec![field], query_tokenizers());
+                (name, parser.parse_query_lenient(query).0)
+            })
+            .collect()
+    }
+
+    /// Score a matched document against each of the per-field queries, so
+    /// results can show which fields contributed and with what boost.
+    fn field_scores(
+        &self,
+        statistics: &DocTypeStatistics,
+        field_queries: &[(&'static str, Box<dyn Query>)],
+        doc_address: DocAddress,
+    ) -> Vec<FieldScore> {
+        let mut scores = Vec::new();
+
+        for (name, field_query) in field_queries {
+            // explain() errors when the document doesn't match this field
+            if let Ok(explanation) = explain(&**field_query, statistics, doc_address) {
+                let boost = self.config.boost(name);
+                scores.push(FieldScore {
+                    field: name.to_string(),
+                    boost,
+                    score: explanation.value() * boost,
+                });
+            }
+        }
+
+        scores
+    }
+}
+
+/// Upper bound on the diff text kept in the index for display.
+const MAX_STORED_DIFF_BYTES: usize = 64 * 1024;
+
+/// Fields that get highlighted excerpts, with the stored field holding their text.
+const SNIPPET_FIELDS: [(&str, &str); 2] = [("description", "description"), ("diff", "stored_diff")];
+const SNIPPET_MAX_CHARS: usize = 200;
+
+const DOC_TYPE_PR: &str = "pr";
+const DOC_TYPE_HUNK: &str = "hunk";
+const DOC_TYPE_REVIEW_COMMENT: &str = "review_comment";
+const DOC_TYPE_CI_FAILURE: &str = "ci_failure";
+
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// `merged`, or the PR's GitHub state (`open` or `closed`) if it wasn't.
+fn pr_status(pr: &PullRequestDetails) -> &str {
+    if pr.merged {
+        "merged"
+    } else {
+        &pr.state
+    }
+}
+
+/// Aggregate a PR's check conclusions into one of `filter::CHECKS_STATUSES`.
+fn checks_status(pr: &PullRequestDetails) -> &'static str {
+    if pr.checks.is_empty() {
+        // No CI reported, or an import that doesn't carry checks
+        "none"
+    } else if pr.checks.iter().all(Check::passed) {
+        "all_passed"
+    } else if pr.checks.iter().any(Check::failed) {
+        "some_failed"
+    } else {
+        "incomplete"
+    }
+}
+
+/// What a PR's vector embeds: its title and description.
+fn embedding_text(title: &str, description: &str) -> String {
+    format!("{}\n{}", title, description)
+}
+
+/// Floor for `recency_factor`, reached after about 20 half-lives. Without
+/// it the factor underflows to zero for old PRs and short half-lives, and
+/// every such PR would score the same.
+const MIN_RECENCY_FACTOR: f32 = 1e-6;
+
+/// `recency_factor` for PRs without a merge time, such as imported dumps
+/// and unmerged PRs: they count as one half-life old, so they neither
+/// outrank every recent PR nor sink below every old one.
+const UNDATED_RECENCY_FACTOR: f32 = 0.5;
+
+/// Score multiplier for a PR merged at `merged_at` (Unix seconds): 1 for
+/// a PR merged now, halving every `half_life_days` down to
+/// `MIN_RECENCY_FACTOR`.
+fn recency_factor(merged_at: Option<i64>, now: i64, half_life_days: f32) -> f32 {
+    let Some(merged_at) = merged_at else {
+        return UNDATED_RECENCY_FACTOR;
+    };
+    let age_days = (now - merged_at).max(0) as f32 / 86_400.0;
+    0.5f32.powf(age_days / half_life_days).max(MIN_RECENCY_FACTOR)
+}
+
+/// Sort byte ranges and merge any that overlap or touch. The code
+/// tokenizer emits a compound identifier and its parts at the same
+/// position, so the snippet generator highlights nested ranges.
+fn merge_ranges(ranges: impl IntoIterator<Item = (usize, usize)>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<_> = ranges.into_iter().collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// `crates/goose-cli/src/main.rs` -> the facet `/crates/goose-cli/src/main.rs`
+fn path_facet(path: &str) -> Facet {
+    Facet::from_path(path.trim_start_matches("./").trim_matches('/').split('/'))
+}
+
+/// Unique key for a PR across repositories, e.g. `block/goose#804`.
+fn pr_key(repo: &str, pr_number: u64) -> String {
+    format!("{}#{}", repo, pr_number)
+}
+
+/// Open the on-disk index, rebuilding it if it was created with an older schema.
+///
+/// The index is only a cache of GitHub data, so a schema change just means
+/// the next sync starts from an empty index.
+/// BM25 statistics over the documents of one type. Each type has text
+/// fields of its own, so term and token counts already leave the others
+/// out; the document count is the one figure that needs narrowing.
+struct DocTypeStatistics<'a> {
+    searcher: &'a Searcher,
+    /// Counted like `Searcher` counts, deleted documents included, so it
+    /// is never below a term's document frequency.
+    num_docs: u64,
+}
+
+impl Bm25StatisticsProvider for DocTypeStatistics<'_> {
+    fn total_num_tokens(&self, field: Field) -> tantivy::Result<u64> {
+        self.searcher.total_num_tokens(field)
+    }
+
+    fn total_num_docs(&self) -> tantivy::Result<u64> {
+        Ok(self.num_docs)
+    }
+
+    /// Terms of fields that every type shares, such as the ones filters
+    /// use, can be in more documents than there are of one type; they
+    /// only ever score zero, so capping them is enough.
+    fn doc_freq(&self, term: &Term) -> tantivy::Result<u64> {
+        Ok(self.searcher.doc_freq(term)?.min(self.num_docs))
+    }
+}
+
+/// `Query::explain`, scoring with `statistics` rather than the whole index.
+fn explain(query: &dyn Query, statistics: &DocTypeStatistics, doc_address: DocAddress) -> tantivy::Result<Explanation> {
+    let weight = query.weight(EnableScoring::enabled_from_statistics_provider(statistics, statistics.searcher))?;
+    weight.explain(statistics.searcher.segment_reader(doc_address.segment_ord), doc_address.doc_id)
+}
+
+/// A query parser over a child document type's own fields, each paired
+/// with the search field whose boost it takes.
+fn child_query_parser(schema: &Schema, config: &SearchConfig, fields: &[(&str, &str)]) -> QueryParser {
+    let field = |name: &str| schema.get_field(name).expect("child search field not found");
+    let mut parser = QueryParser::new(
+        schema.clone(),
+        fields.iter().map(|(name, _)| field(name)).collect(),
+        query_tokenizers(),
+    );
+    for (name, boost_from) in fields {
+        parser.set_field_boost(field(name), config.boost(boost_from));
+    }
+    parser
+}
+
+fn open_index(cache_dir: &std::path::Path, schema: Schema) -> Result<Index> {
+    std::fs::create_dir_all(cache_dir)?;
+    let dir = MmapDirectory::open(cache_dir)?;
+    match Index::open_or_create(dir, schema.clone()) {
+        Err(TantivyError::SchemaError(_)) => {
+            eprintln!(
+                "The index in {} has an outdated schema and is being rebuilt from scratch. \
+                 Synced repositories are fetched again on the next sync; imported dumps need --import again.",
+                cache_dir.display()
+            );
+            // Dropping meta.json orphans the old segments; tantivy's garbage
+            // collection removes them once the new index is committed.
+            std::fs::remove_file(cache_dir.join("meta.json"))?;
+            let dir = MmapDirectory::open(cache_dir)?;
+            Ok(Index::open_or_create(dir, schema)?)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Everything the index keeps about a PR, available offline.
+#[derive(Debug, Serialize)]
+pub struct StoredPr {
+    pub repo: String,
+    pub number: u64,
+    pub title: String,
+    pub url: String,
+    pub status: String,
+    pub checks_status: String,
+    pub description: String,
+    pub files: Vec<String>,
+    pub commits: Vec<String>,
+    /// Issue comments on the PR.
+    pub comments: Vec<String>,
+    /// Bodies of inline review comments.
+    pub review_comments: Vec<String>,
+    /// The diff, cut off after a fixed size.
+    pub diff: String,
+    /// Whether `diff` is shorter than the PR's full diff.
+    pub diff_truncated: bool,
+}
+
+/// An excerpt of a field with the byte ranges of matched terms.
+#[derive(Debug, Serialize)]
+pub struct SearchSnippet {
+    pub field: String,
+    pub fragment: String,
+    pub highlighted: Vec<(usize, usize)>,
+}
+
+impl SearchSnippet {
+    /// The fragment on one line with matches wrapped in `marker`.
+    /// `highlighted` is expected sorted; ranges already covered are skipped.
+    pub fn marked(&self, marker: &str) -> String {
+        let mut out = String::new();
+        let mut last = 0;
+        for &(start, end) in &self.highlighted {
+            if end <= last {
+                continue;
+            }
+            let start = start.max(last);
+            out.push_str(&self.fragment[last..start]);
+            out.push_str(marker);
+            out.push_str(&self.fragment[start..end]);
+            out.push_str(marker);
+            last = end;
+        }
+        out.push_str(&self.fragment[last..]);
+        out.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+pub struct FieldScore {
+    pub field: String,
+    pub boost: f32,
+    pub score: f32,
+}
+
+/// A matching diff hunk, located by file and line range.
+#[derive(Debug, Serialize)]
+pub struct HunkResult {
+    pub repo: String,
+    pub pr_number: u64,
+    pub path: String,
+    pub old_start: u64,
+    pub old_lines: u64,
+    pub new_start: u64,
+    pub new_lines: u64,
+    pub header: String,
+    pub score: f32,
+}
+
+impl HunkResult {
+    /// Last line of the hunk in the new version of the file.
+    pub fn new_end(&self) -> u64 {
+        self.new_start + self.new_lines.saturating_sub(1)
+    }
+}
+
+/// A matching inline review comment and where it was made.
+#[derive(Debug, Serialize)]
+pub struct ReviewCommentResult {
+    pub repo: String,
+    pub pr_number: u64,
+    pub path: String,
+    pub line: Option<u64>,
+    pub body: String,
+    pub url: String,
+    pub score: f32,
+}
+
+/// An indexed CI failure resembling a new one, and how its PR fixed it.
+#[derive(Debug, Serialize)]
+pub struct FailureMatch {
+    pub repo: String,
+    pub pr_number: u64,
+    pub title: String,
+    /// `workflow / job / step` of the failed check.
+    pub check: String,
+    pub failed_commit: String,
+    /// `sha summary` of each commit pushed after the failure.
+    pub fixing_commits: Vec<String>,
+    /// The normalized log excerpt that matched.
+    pub signature: String,
+    pub url: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
 pub struct SearchResult {
+    pub repo: String,
     pub pr_number: u64,
     pub title: String,
+    pub url: String,
     pub status: String,
     pub checks_status: String,
     pub files: Vec<String>,
+    pub merged_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Final ranking score.
     pub score: f32,
+    pub bm25_score: f32,
+    /// Cosine similarity to the query, when semantic reranking ran.
+    pub semantic_score: Option<f32>,
+    /// Time-decay multiplier applied to the score, when recency ranking is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recency_factor: Option<f32>,
+    pub field_scores: Vec<FieldScore>,
+    /// Excerpts around the matched terms.
+    pub snippets: Vec<SearchSnippet>,
+    /// Files this PR shares with the query, for file-overlap searches.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared_files: Vec<String>,
 }
 
-fn extract_files_from_diff(diff: &str) -> Vec<String> {
-    diff.lines()
-        .filter(|line| line.starts_with("diff --git"))
-        .filter_map(|line| {
-            line.split(" b/").nth(1).map(String::from)
-        })
-        .collect()
-}
\ No newline at end of file
+/// Paths of the files changed in a unified diff: `git diff` output, or a
+/// plain unified diff (`diff -u`, `svn diff`) with only `---`/`+++` headers.
+pub fn extract_files_from_diff(diff: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    // Lines of the current hunk still to come; inside a hunk, a removed
+    // `-- comment` line looks just like a `---` header
+    let (mut old_left, mut new_left): (u64, u64) = (0, 0);
+    let mut git_header = false;
+    let mut old_path = None;
+
+    for line in diff.lines() {
+        if old_left > 0 || new_left > 0 {
+            let (old, new) = match line.chars().next() {
+                Some('-') => (1, 0),
+                Some('+') => (0, 1),
+                Some('\\') => (0, 0),
+                _ => (1, 1),
+            };
+            old_left = old_left.saturating_sub(old);
+            new_left = new_left.saturating_sub(new);
+        } else if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.extend(rest.split(" b/").nth(1).map(String::from));
+            git_header = true;
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            old_path = header_path(path, "a/");
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            // A deleted file's new path is /dev/null
+            let path = header_path(path, "b/").or(old_path.take());
+            if !git_header {
+                files.extend(path);
+            }
+            git_header = false;
+        } else if line.starts_with("@@") {
+            if let Some((_, old_lines, _, new_lines)) = parse_hunk_header(line) {
+                (old_left, new_left) = (old_lines, new_lines);
+            }
+        }
+    }
+    files
+}
+
+/// The path in a `---`/`+++` header, without git's `a/`/`b/` prefix or
+/// the timestamp `diff -u` appends after a tab; `None` for /dev/null.
+fn header_path(header: &str, git_prefix: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path.is_empty() || path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(git_prefix).unwrap_or(path).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+
+    #[test]
+    fn recency_factor_halves_every_half_life() {
+        let now = 1_700_000_000;
+        assert_eq!(recency_factor(Some(now), now, 30.0), 1.0);
+        assert!((recency_factor(Some(now - 30 * DAY), now, 30.0) - 0.5).abs() < 1e-6);
+        assert!((recency_factor(Some(now - 60 * DAY), now, 30.0) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recency_factor_of_undated_and_future_merges() {
+        let now = 1_700_000_000;
+        assert_eq!(recency_factor(None, now, 30.0), UNDATED_RECENCY_FACTOR);
+        assert_eq!(recency_factor(Some(now + DAY), now, 30.0), 1.0);
+    }
+
+    #[test]
+    fn files_from_git_diff() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                    index 1111111..2222222 100644\n\
+                    --- a/src/lib.rs\n\
+                    +++ b/src/lib.rs\n\
+                    @@ -1,2 +1,2 @@\n\
+                    -old\n\
+                    +new\n\
+                    \x20context\n\
+                    diff --git a/logo.png b/logo.png\n\
+                    Binary files a/logo.png and b/logo.png differ\n";
+        assert_eq!(extract_files_from_diff(diff), ["src/lib.rs", "logo.png"]);
+    }
+
+    #[test]
+    fn files_from_plain_unified_diff() {
+        // `diff -u` headers carry timestamps; a removed SQL comment looks like a header
+        let diff = "--- migrations/001.sql\t2024-05-01 10:00:00.000000000 +0200\n\
+                    +++ migrations/001.sql\t2024-05-02 11:00:00.000000000 +0200\n\
+                    @@ -1,2 +1 @@\n\
+                    --- drop me\n\
+                    -+++ and me\n\
+                    +CREATE TABLE t (id INT);\n\
+                    --- src/old.rs\n\
+                    +++ /dev/null\n\
+                    @@ -1 +0,0 @@\n\
+                    -fn old() {}\n\
+                    Index: src/app.rs\n\
+                    ===================================================================\n\
+                    --- src/app.rs\t(revision 42)\n\
+                    +++ src/app.rs\t(working copy)\n\
+                    @@ -1 +1 @@\n\
+                    -a\n\
+                    +b\n\
+                    \\ No newline at end of file\n";
+        assert_eq!(
+            extract_files_from_diff(diff),
+            ["migrations/001.sql", "src/old.rs", "src/app.rs"]
+        );
+    }
+
+    #[test]
+    fn recency_factor_stays_positive_for_old_prs() {
+        let now = 1_700_000_000;
+        let factor = recency_factor(Some(now - 3650 * DAY), now, 0.5);
+        assert_eq!(factor, MIN_RECENCY_FACTOR);
+        assert!((10.0 / factor).is_finite());
+    }
+}
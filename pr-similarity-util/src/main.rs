@@ -1,40 +1,202 @@
-use anyhow::Result;
-use clap::Parser;
-use pr_similarity_search::PRSearchIndex;
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use pr_similarity_search::ci_failure::{failure_signature, find_ci_failures, DEFAULT_CONTEXT};
+use pr_similarity_search::config::{parse_field_boost, SearchConfig};
+use pr_similarity_search::filter::{parse_date, parse_end_date, SearchFilters, CHECKS_STATUSES, PR_STATES};
+use pr_similarity_search::github::GitHubClient;
+use pr_similarity_search::import::read_pr_dumps;
+use pr_similarity_search::output::{
+    render, render_failures, render_pr, render_related, render_similar_failures, OutputFormat, RelatedFilesReport,
+    SearchReport, SimilarFailuresReport,
+};
+use pr_similarity_search::query::{issue_query, parse_issue_ref};
+use pr_similarity_search::sync_state::SyncOptions;
+use pr_similarity_search::{extract_files_from_diff, PRSearchIndex, RepoFilter};
+use std::io::Read;
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// GitHub repository owner
-    #[arg(short, long)]
-    owner: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// GitHub repository name
-    #[arg(short, long)]
-    repo: String,
+    /// Default owner for repositories given without one
+    #[arg(short, long, global = true)]
+    owner: Option<String>,
+
+    /// Repository to sync and search, as `name` or `owner/name` (repeatable)
+    #[arg(short, long, global = true)]
+    repo: Vec<String>,
+
+    /// GitHub API root, or a GitHub Enterprise Server host such as
+    /// https://ghe.example.com [default: $GITHUB_API_URL or https://api.github.com]
+    #[arg(long, value_name = "URL", global = true)]
+    api_url: Option<String>,
+
+    /// Search every repository in the index, not just those given with --repo
+    #[arg(long, global = true)]
+    all_repos: bool,
+
+    /// Index `gh pr view` dumps from a file or directory (repeatable)
+    #[arg(long, value_name = "PATH")]
+    import: Vec<PathBuf>,
+
+    /// Use only the local index; don't sync with GitHub
+    #[arg(long, global = true)]
+    offline: bool,
 
     /// Cache directory for PR data
-    #[arg(short, long, default_value = ".pr_cache")]
+    #[arg(short, long, default_value = ".pr_cache", global = true)]
     cache_dir: PathBuf,
 
     /// Search query
     #[arg(short, long)]
     query: Option<String>,
 
-    /// Number of PRs to fetch (max 100)
+    /// Search for PRs similar to a GitHub issue, given as a URL or a number
+    /// in the first --repo
+    #[arg(short, long, conflicts_with = "query")]
+    issue: Option<String>,
+
+    /// Maximum number of PRs to fetch when a repository is first synced;
+    /// later syncs fetch everything merged since
     #[arg(short, long, default_value = "100")]
     limit: usize,
 
+    /// Number of PR fetches (single PRs or GraphQL batches) run at once during a sync
+    #[arg(long, default_value = "8")]
+    concurrency: usize,
+
+    /// Commit the index after every this many PRs fetched during a sync
+    #[arg(long, default_value = "25")]
+    commit_every: usize,
+
+    /// PRs fetched per GraphQL query during a sync (0 to use the REST API)
+    #[arg(long, default_value = "10")]
+    graphql_batch_size: usize,
+
+    /// During a sync, also index the CI failures each PR fixed before merging
+    #[arg(long)]
+    index_failures: bool,
+
+    /// Also index PRs that were closed without merging or are still open
+    #[arg(long)]
+    include_unmerged: bool,
+
     /// Number of results to show
-    #[arg(short = 'n', long, default_value = "5")]
+    #[arg(short = 'n', long, default_value = "5", global = true)]
     num_results: usize,
 
-    /// Force refresh of PR cache
+    /// Find PRs that changed these files instead of running a text query
+    /// (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["query", "issue"])]
+    files: Vec<String>,
+
+    /// Find PRs that changed the same files as this unified diff (`-` for stdin)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["query", "issue", "files"])]
+    diff: Option<PathBuf>,
+
+    /// Only PRs merged on or after this date (YYYY-MM-DD, or an age like 90d)
+    #[arg(long, value_name = "DATE", value_parser = parse_date, global = true)]
+    merged_after: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Only PRs merged on or before this date (YYYY-MM-DD, or an age like 90d);
+    /// a bare date includes the whole day
+    #[arg(long, value_name = "DATE", value_parser = parse_end_date, global = true)]
+    merged_before: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Only PRs opened by this GitHub user
+    #[arg(long, global = true)]
+    author: Option<String>,
+
+    /// Only PRs with this label (repeatable; all must match)
+    #[arg(long = "label", value_name = "LABEL", global = true)]
+    labels: Vec<String>,
+
+    /// Only PRs against this base branch
+    #[arg(long, value_name = "BRANCH", global = true)]
+    base: Option<String>,
+
+    /// Only PRs whose checks ended in this state; `none` for PRs without checks
+    #[arg(long, value_parser = CHECKS_STATUSES, global = true)]
+    checks: Option<String>,
+
+    /// Only PRs that changed this file or something under this directory
+    #[arg(long = "path", value_name = "PREFIX", global = true)]
+    path_prefix: Option<String>,
+
+    /// Only PRs in this state (repeatable). Without it, merged PRs are ranked
+    /// and unmerged ones are listed separately
+    #[arg(long = "state", value_name = "STATE", value_parser = PR_STATES, global = true)]
+    states: Vec<String>,
+
+    /// Output format for search results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    format: OutputFormat,
+
+    /// Number of matching diff hunks to show (0 to skip hunk search)
+    #[arg(long, default_value = "5")]
+    num_hunks: usize,
+
+    /// Number of matching review comments to show (0 to skip)
+    #[arg(long, default_value = "3")]
+    num_review_comments: usize,
+
+    /// JSON file with search settings such as field boosts
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Boost a search field, e.g. `--boost title=3` (repeatable)
+    #[arg(long = "boost", value_name = "FIELD=BOOST", value_parser = parse_field_boost)]
+    boosts: Vec<(String, f32)>,
+
+    /// Local word-embedding file (GloVe / fastText .vec) for semantic reranking
+    #[arg(long)]
+    embedding_model: Option<PathBuf>,
+
+    /// Share of the score taken from embedding similarity (0-1)
+    #[arg(long)]
+    semantic_weight: Option<f32>,
+
+    /// Halve a PR's score for every this many days since it was merged;
+    /// PRs without a merge date count as this many days old
+    #[arg(long, value_name = "DAYS")]
+    half_life: Option<f32>,
+
+    /// Discard the PR cache and rebuild it from scratch
     #[arg(short, long)]
     force_refresh: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// List the files most often changed in the same PRs as a file
+    RelatedFiles {
+        /// Repository-relative path of the file
+        path: String,
+    },
+    /// Print a PR from the local index, without contacting GitHub
+    Show {
+        /// PR number
+        number: u64,
+    },
+    /// Download the logs of a PR's failed CI runs and report what failed
+    CiFailures {
+        /// PR number in the first --repo
+        number: u64,
+        /// Log lines to keep after the start of each error
+        #[arg(long, default_value_t = DEFAULT_CONTEXT)]
+        context: usize,
+    },
+    /// Find indexed CI failures like a log excerpt, and the PRs that fixed them
+    SimilarFailures {
+        /// File with the failing log excerpt (`-` for stdin)
+        #[arg(default_value = "-")]
+        log: PathBuf,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -44,39 +206,219 @@ async fn main() -> Result<()> {
         std::fs::create_dir_all(&cli.cache_dir)?;
     }
 
+    let mut config = match &cli.config {
+        Some(path) => SearchConfig::load(path)?,
+        None => SearchConfig::default(),
+    };
+    for (field, boost) in &cli.boosts {
+        config.set_boost(field, *boost)?;
+    }
+    if let Some(path) = &cli.embedding_model {
+        config.embedding_model = Some(path.clone());
+    }
+    if let Some(weight) = cli.semantic_weight {
+        config.set_semantic_weight(weight)?;
+    }
+    if let Some(days) = cli.half_life {
+        config.set_recency_half_life(days)?;
+    }
+
+    let mut repos = cli.repo.clone();
+    let issue_ref = cli.issue.as_deref().map(parse_issue_ref).transpose()?;
+    if let Some((Some(issue_repo), _)) = &issue_ref {
+        if !repos.iter().any(|r| r == issue_repo) {
+            repos.push(issue_repo.clone());
+        }
+    }
+
+    // These only read what is already indexed: no GitHub access, no sync
+    let reads_index_only = matches!(cli.command, Some(Command::Show { .. } | Command::RelatedFiles { .. }));
+    let offline = cli.offline || reads_index_only;
+
+    let mut clients = Vec::new();
+    let mut repo_names = Vec::new();
+    for repo in &repos {
+        let (owner, name) = match repo.split_once('/') {
+            Some((owner, name)) => (owner.to_string(), name.to_string()),
+            None => {
+                let owner = cli.owner.clone().ok_or_else(|| {
+                    anyhow!("repository '{}' has no owner; use owner/name or --owner", repo)
+                })?;
+                (owner, repo.clone())
+            }
+        };
+        let full_name = format!("{}/{}", owner, name);
+        if repo_names.contains(&full_name) {
+            continue;
+        }
+        repo_names.push(full_name);
+        if !offline {
+            let mut client = GitHubClient::new(owner, name)?.with_response_cache(cli.cache_dir.join("http"));
+            if let Some(url) = &cli.api_url {
+                client = client.with_api_url(url);
+            }
+            clients.push(client);
+        }
+    }
+
+    if let Some(Command::CiFailures { number, context }) = &cli.command {
+        let client = clients
+            .first()
+            .ok_or_else(|| anyhow!("ci-failures needs a --repo and GitHub access"))?;
+        let report = find_ci_failures(client, *number, *context).await?;
+        print!("{}", render_failures(cli.format, &report)?);
+        return Ok(());
+    }
+
+    let mut query = cli.query.clone();
+    if let Some((issue_repo, number)) = &issue_ref {
+        let issue_repo = issue_repo
+            .as_ref()
+            .or(repo_names.first())
+            .ok_or_else(|| anyhow!("--issue needs a full issue URL or a --repo"))?;
+        let client = clients
+            .iter()
+            .find(|c| &c.full_name() == issue_repo)
+            .ok_or_else(|| anyhow!("--issue needs GitHub access; drop --offline"))?;
+        let issue = client.get_issue(*number).await?;
+        eprintln!("Issue #{}: {}", issue.number, issue.title);
+        query = Some(issue_query(&issue));
+    }
+
+    let filters = SearchFilters {
+        repos: if cli.all_repos || repo_names.is_empty() {
+            RepoFilter::All
+        } else {
+            RepoFilter::Only(repo_names)
+        },
+        merged_after: cli.merged_after,
+        merged_before: cli.merged_before,
+        author: cli.author.clone(),
+        labels: cli.labels.clone(),
+        base_branch: cli.base.clone(),
+        checks_status: cli.checks.clone(),
+        path_prefix: cli.path_prefix.clone(),
+        states: cli.states.clone(),
+    };
+    let (filters, unmerged_filters) = filters.merged_and_unmerged();
+
     // Initialize search index
-    let mut search_index = PRSearchIndex::new(cli.owner, cli.repo, &cli.cache_dir)?;
+    let mut search_index = PRSearchIndex::with_config(clients, &cli.cache_dir, config)?;
+    search_index.set_sync_options(SyncOptions {
+        concurrency: cli.concurrency,
+        commit_every: cli.commit_every,
+        graphql_batch_size: cli.graphql_batch_size,
+        index_failures: cli.index_failures,
+        include_unmerged: cli.include_unmerged,
+    });
+
+    for path in &cli.import {
+        let prs = read_pr_dumps(path)?;
+        let imported = search_index.import_prs(&prs)?;
+        eprintln!("Imported {} PRs from {} ({} dumps read)", imported, path.display(), prs.len());
+    }
+
+    if let Some(Command::Show { number }) = &cli.command {
+        let pr = search_index
+            .get_pr_in(*number, &filters.repos)?
+            .ok_or_else(|| anyhow!("PR #{} is not in the index", number))?;
+        print!("{}", render_pr(cli.format, &pr)?);
+        return Ok(());
+    }
+
+    if let Some(Command::RelatedFiles { path }) = &cli.command {
+        let graph = search_index.co_change_graph(&filters)?;
+        let path = path.trim_start_matches("./").to_string();
+        let report = RelatedFilesReport {
+            change_count: graph.change_count(&path),
+            related: graph.related(&path, cli.num_results),
+            path,
+        };
+        print!("{}", render_related(cli.format, &report)?);
+        return Ok(());
+    }
 
     // Load PRs (from cache or GitHub)
-    println!("Loading PRs{}...", if cli.force_refresh { " (forced refresh)" } else { "" });
-    let prs = search_index.load_recent_prs(cli.limit, cli.force_refresh).await?;
-    println!("Loaded {} PRs", prs.len());
-
-    // If a query is provided, perform the search
-    if let Some(query) = cli.query {
-        println!("\nSearching for PRs matching: {}", query);
-        println!("----------------------------------------");
-
-        let results = search_index.search(&query, cli.num_results)?;
-        
-        if results.is_empty() {
-            println!("No matching PRs found.");
+    if !offline && !repos.is_empty() {
+        eprintln!("Loading PRs{}...", if cli.force_refresh { " (forced refresh)" } else { "" });
+        let prs = search_index.load_recent_prs(cli.limit, cli.force_refresh).await?;
+        eprintln!("Loaded {} new PRs", prs.len());
+    }
+
+    if let Some(Command::SimilarFailures { log }) = &cli.command {
+        let excerpt = if log.as_os_str() == "-" {
+            let mut excerpt = String::new();
+            std::io::stdin().read_to_string(&mut excerpt)?;
+            excerpt
         } else {
-            for result in results {
-                println!("\nPR #{}: {} (Score: {:.3})", result.pr_number, result.title, result.score);
-                println!("Status: {} (Checks: {})", result.status, result.checks_status);
-                println!("Modified files:");
-                for file in result.files.iter().take(5) {
-                    println!("  - {}", file);
-                }
-                if result.files.len() > 5 {
-                    println!("  ... and {} more files", result.files.len() - 5);
-                }
-            }
+            std::fs::read_to_string(log)?
+        };
+        let report = SimilarFailuresReport {
+            signature: failure_signature(&excerpt),
+            matches: search_index.similar_failures(&excerpt, cli.num_results, &filters)?,
+        };
+        print!("{}", render_similar_failures(cli.format, &report)?);
+        return Ok(());
+    }
+
+    let mut files = cli.files.clone();
+    if let Some(path) = &cli.diff {
+        let diff = if path.as_os_str() == "-" {
+            let mut diff = String::new();
+            std::io::stdin().read_to_string(&mut diff)?;
+            diff
+        } else {
+            std::fs::read_to_string(path)?
+        };
+        files = extract_files_from_diff(&diff);
+        if files.is_empty() {
+            return Err(anyhow!("no changed files found in {}", path.display()));
         }
+    }
+
+    if !files.is_empty() {
+        // File-overlap search needs no text query
+        let results = search_index.search_by_files(&files, cli.num_results, &filters)?;
+        let unmerged = match &unmerged_filters {
+            Some(unmerged_filters) => search_index.search_by_files(&files, cli.num_results, unmerged_filters)?,
+            None => Vec::new(),
+        };
+        let report = SearchReport {
+            query: format!("files: {}", files.join(", ")),
+            results,
+            unmerged,
+            hunks: Vec::new(),
+            review_comments: Vec::new(),
+        };
+        print!("{}", render(cli.format, &report)?);
+    } else if let Some(query) = query {
+        let results = search_index.search_in(&query, cli.num_results, &filters)?;
+        let unmerged = match &unmerged_filters {
+            Some(unmerged_filters) => search_index.search_in(&query, cli.num_results, unmerged_filters)?,
+            None => Vec::new(),
+        };
+        let hunks = if cli.num_hunks > 0 {
+            search_index.search_hunks(&query, cli.num_hunks, &filters)?
+        } else {
+            Vec::new()
+        };
+        let review_comments = if cli.num_review_comments > 0 {
+            search_index.search_review_comments(&query, cli.num_review_comments, &filters)?
+        } else {
+            Vec::new()
+        };
+
+        let report = SearchReport {
+            query,
+            results,
+            unmerged,
+            hunks,
+            review_comments,
+        };
+        print!("{}", render(cli.format, &report)?);
     } else {
-        println!("No search query provided. Use --query to search for similar PRs.");
+        eprintln!("No search query provided. Use --query, --issue, --files or --diff to search for similar PRs.");
     }
 
     Ok(())
-}
\ No newline at end of file
+}
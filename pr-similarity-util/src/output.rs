@@ -0,0 +1,755 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::ci_failure::{failure_title, CiFailureReport};
+use crate::cochange::RelatedFile;
+use crate::{FailureMatch, HunkResult, ReviewCommentResult, SearchResult, StoredPr};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable summary
+    #[default]
+    Text,
+    /// A single JSON document with results and hunks
+    Json,
+    /// One JSON search result per line
+    Jsonl,
+    /// Markdown suited to an agent context file
+    Markdown,
+}
+
+/// Everything a search produced, ready to be rendered.
+#[derive(Debug, Serialize)]
+pub struct SearchReport {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+    /// Closed-unmerged and open PRs, ranked apart from `results`.
+    pub unmerged: Vec<SearchResult>,
+    pub hunks: Vec<HunkResult>,
+    pub review_comments: Vec<ReviewCommentResult>,
+}
+
+/// Files that change together with `path`.
+#[derive(Debug, Serialize)]
+pub struct RelatedFilesReport {
+    pub path: String,
+    /// Number of indexed PRs that changed `path`.
+    pub change_count: usize,
+    pub related: Vec<RelatedFile>,
+}
+
+/// Indexed CI failures that match a new log excerpt.
+#[derive(Debug, Serialize)]
+pub struct SimilarFailuresReport {
+    /// The excerpt as normalized for matching.
+    pub signature: String,
+    pub matches: Vec<FailureMatch>,
+}
+
+pub fn render(format: OutputFormat, report: &SearchReport) -> Result<String> {
+    match format {
+        OutputFormat::Text => Ok(render_text(report)),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(report)? + "\n"),
+        OutputFormat::Jsonl => {
+            let mut out = String::new();
+            for result in report.results.iter().chain(&report.unmerged) {
+                out.push_str(&serde_json::to_string(result)?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        OutputFormat::Markdown => Ok(render_markdown(report)),
+    }
+}
+
+fn render_text(report: &SearchReport) -> String {
+    let mut out = String::new();
+    writeln!(out, "\nSearching for PRs matching: {}", report.query).unwrap();
+    writeln!(out, "----------------------------------------").unwrap();
+
+    if report.results.is_empty() {
+        writeln!(out, "No matching PRs found.").unwrap();
+    }
+    for result in &report.results {
+        write_result_text(&mut out, result);
+    }
+
+    if !report.unmerged.is_empty() {
+        writeln!(out, "\nUnmerged PRs (closed without merging or still open):").unwrap();
+        writeln!(out, "----------------------------------------").unwrap();
+        for result in &report.unmerged {
+            write_result_text(&mut out, result);
+        }
+    }
+
+    if !report.hunks.is_empty() {
+        writeln!(out, "\nMatching code regions:").unwrap();
+        for hunk in &report.hunks {
+            writeln!(
+                out,
+                "  {}#{} {}:{}-{} (Score: {:.3})",
+                hunk.repo,
+                hunk.pr_number,
+                hunk.path,
+                hunk.new_start,
+                hunk.new_end(),
+                hunk.score
+            )
+            .unwrap();
+            writeln!(out, "    {}", hunk.header).unwrap();
+        }
+    }
+
+    if !report.review_comments.is_empty() {
+        writeln!(out, "\nMatching review comments:").unwrap();
+        for comment in &report.review_comments {
+            writeln!(
+                out,
+                "  {}#{} {} (Score: {:.3})",
+                comment.repo,
+                comment.pr_number,
+                comment_location(comment),
+                comment.score
+            )
+            .unwrap();
+            writeln!(out, "    {}", first_line(&comment.body)).unwrap();
+        }
+    }
+
+    out
+}
+
+fn write_result_text(out: &mut String, result: &SearchResult) {
+    writeln!(
+        out,
+        "\n{}#{}: {} (Score: {:.3})",
+        result.repo, result.pr_number, result.title, result.score
+    )
+    .unwrap();
+    writeln!(out, "Status: {} (Checks: {})", status_label(&result.status), result.checks_status).unwrap();
+    if let Some(merged_at) = result.merged_at {
+        writeln!(out, "Merged: {}", merged_at.format("%Y-%m-%d")).unwrap();
+    }
+    if let Some(breakdown) = score_breakdown(result) {
+        writeln!(out, "Score breakdown: {}", breakdown).unwrap();
+    }
+    if !result.field_scores.is_empty() {
+        let breakdown: Vec<_> = result
+            .field_scores
+            .iter()
+            .map(|f| format!("{} {:.3} (x{})", f.field, f.score, f.boost))
+            .collect();
+        writeln!(out, "Matched: {}", breakdown.join(", ")).unwrap();
+    }
+    for snippet in &result.snippets {
+        writeln!(out, "  [{}] {}", snippet.field, snippet.marked("**")).unwrap();
+    }
+    if !result.shared_files.is_empty() {
+        writeln!(out, "Shared files: {}", result.shared_files.join(", ")).unwrap();
+    }
+    writeln!(out, "Modified files:").unwrap();
+    for file in result.files.iter().take(5) {
+        writeln!(out, "  - {}", file).unwrap();
+    }
+    if result.files.len() > 5 {
+        writeln!(out, "  ... and {} more files", result.files.len() - 5).unwrap();
+    }
+}
+
+fn render_markdown(report: &SearchReport) -> String {
+    let mut out = String::new();
+    writeln!(out, "## Similar pull requests\n").unwrap();
+    writeln!(out, "Query: `{}`\n", report.query).unwrap();
+
+    if report.results.is_empty() {
+        writeln!(out, "No matching PRs found.").unwrap();
+    }
+    for result in &report.results {
+        write_result_markdown(&mut out, result);
+    }
+
+    if !report.unmerged.is_empty() {
+        writeln!(out, "## Unmerged pull requests\n").unwrap();
+        writeln!(out, "Closed without merging or still open; not approaches that landed.\n").unwrap();
+        for result in &report.unmerged {
+            write_result_markdown(&mut out, result);
+        }
+    }
+
+    if !report.hunks.is_empty() {
+        writeln!(out, "### Matching code regions\n").unwrap();
+        for hunk in &report.hunks {
+            writeln!(
+                out,
+                "- `{}:{}-{}` in {}#{} (`{}`)",
+                hunk.path,
+                hunk.new_start,
+                hunk.new_end(),
+                hunk.repo,
+                hunk.pr_number,
+                hunk.header
+            )
+            .unwrap();
+        }
+    }
+
+    if !report.review_comments.is_empty() {
+        writeln!(out, "\n### Review discussion\n").unwrap();
+        for comment in &report.review_comments {
+            writeln!(
+                out,
+                "- [{}#{}]({}) on `{}`: {}",
+                comment.repo,
+                comment.pr_number,
+                comment.url,
+                comment_location(comment),
+                first_line(&comment.body)
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+fn write_result_markdown(out: &mut String, result: &SearchResult) {
+    writeln!(
+        out,
+        "### [{}#{}]({}): {}\n",
+        result.repo, result.pr_number, result.url, result.title
+    )
+    .unwrap();
+    writeln!(out, "- Status: {} (checks: {})", status_label(&result.status), result.checks_status).unwrap();
+    if let Some(merged_at) = result.merged_at {
+        writeln!(out, "- Merged: {}", merged_at.format("%Y-%m-%d")).unwrap();
+    }
+    match score_breakdown(result) {
+        Some(breakdown) => writeln!(out, "- Score: {:.3} ({})", result.score, breakdown).unwrap(),
+        None => writeln!(out, "- Score: {:.3}", result.score).unwrap(),
+    }
+    if !result.shared_files.is_empty() {
+        writeln!(out, "- Shared files: `{}`", result.shared_files.join("`, `")).unwrap();
+    }
+    for snippet in &result.snippets {
+        writeln!(out, "- Excerpt ({}): {}", snippet.field, snippet.marked("**")).unwrap();
+    }
+    if !result.files.is_empty() {
+        writeln!(out, "- Files:").unwrap();
+        for file in &result.files {
+            writeln!(out, "  - `{}`", file).unwrap();
+        }
+    }
+    out.push('\n');
+}
+
+/// A PR's indexed status, spelled out for unmerged PRs.
+fn status_label(status: &str) -> &str {
+    match status {
+        "closed" => "closed without merging",
+        "open" => "open, not merged",
+        other => other,
+    }
+}
+
+/// How the final score was put together, when more than BM25 went into it.
+fn score_breakdown(result: &SearchResult) -> Option<String> {
+    if result.semantic_score.is_none() && result.recency_factor.is_none() {
+        return None;
+    }
+    let mut parts = vec![format!("BM25 {:.3}", result.bm25_score)];
+    if let Some(semantic) = result.semantic_score {
+        parts.push(format!("semantic similarity {:.3}", semantic));
+    }
+    if let Some(factor) = result.recency_factor {
+        parts.push(format!("recency x{:.3}", factor));
+    }
+    Some(parts.join(", "))
+}
+
+/// `path:line`, or just the path for comments without a line.
+fn comment_location(comment: &ReviewCommentResult) -> String {
+    match comment.line {
+        Some(line) => format!("{}:{}", comment.path, line),
+        None => comment.path.clone(),
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+pub fn render_related(format: OutputFormat, report: &RelatedFilesReport) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Json => return Ok(serde_json::to_string_pretty(report)? + "\n"),
+        OutputFormat::Jsonl => {
+            for file in &report.related {
+                out.push_str(&serde_json::to_string(file)?);
+                out.push('\n');
+            }
+        }
+        OutputFormat::Text => {
+            writeln!(out, "Files changed together with {} ({} PRs):", report.path, report.change_count).unwrap();
+            if report.related.is_empty() {
+                writeln!(out, "  none found").unwrap();
+            }
+            for file in &report.related {
+                writeln!(
+                    out,
+                    "  {} (confidence {:.2}, support {:.3}, {} PRs)",
+                    file.path, file.confidence, file.support, file.co_changes
+                )
+                .unwrap();
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "## Files changed together with `{}`\n", report.path).unwrap();
+            writeln!(out, "| File | Confidence | Support | PRs |").unwrap();
+            writeln!(out, "|------|------------|---------|-----|").unwrap();
+            for file in &report.related {
+                writeln!(
+                    out,
+                    "| `{}` | {:.2} | {:.3} | {} |",
+                    file.path, file.confidence, file.support, file.co_changes
+                )
+                .unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub fn render_failures(format: OutputFormat, report: &CiFailureReport) -> Result<String> {
+    let mut out = String::new();
+    let head = short_sha(&report.head_sha);
+    match format {
+        OutputFormat::Json => return Ok(serde_json::to_string_pretty(report)? + "\n"),
+        OutputFormat::Jsonl => {
+            for failure in &report.failures {
+                out.push_str(&serde_json::to_string(failure)?);
+                out.push('\n');
+            }
+        }
+        OutputFormat::Text => {
+            writeln!(out, "CI failures for {}#{} at {}:", report.repo, report.pr_number, head).unwrap();
+            if report.failures.is_empty() {
+                writeln!(out, "  no failed checks").unwrap();
+            }
+            for failure in &report.failures {
+                match &failure.location {
+                    Some(location) => writeln!(out, "\n{} ({})", failure_title(failure), location).unwrap(),
+                    None => writeln!(out, "\n{}", failure_title(failure)).unwrap(),
+                }
+                writeln!(out, "  {}", failure.url).unwrap();
+                for line in &failure.excerpt {
+                    writeln!(out, "  | {}", line).unwrap();
+                }
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "## CI failures for {}#{} at `{}`\n", report.repo, report.pr_number, head).unwrap();
+            if report.failures.is_empty() {
+                writeln!(out, "No failed checks.").unwrap();
+            }
+            for failure in &report.failures {
+                writeln!(out, "### {}\n", failure_title(failure)).unwrap();
+                if let Some(location) = &failure.location {
+                    writeln!(out, "- Location: `{}`", location).unwrap();
+                }
+                writeln!(out, "- Details: {}\n", failure.url).unwrap();
+                if !failure.excerpt.is_empty() {
+                    let excerpt = failure.excerpt.join("\n");
+                    let fence = fence(&excerpt);
+                    writeln!(out, "{}text\n{}\n{}\n", fence, excerpt, fence).unwrap();
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub fn render_similar_failures(format: OutputFormat, report: &SimilarFailuresReport) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Json => return Ok(serde_json::to_string_pretty(report)? + "\n"),
+        OutputFormat::Jsonl => {
+            for failure in &report.matches {
+                out.push_str(&serde_json::to_string(failure)?);
+                out.push('\n');
+            }
+        }
+        OutputFormat::Text => {
+            writeln!(out, "CI failures like: {}", first_line(&report.signature)).unwrap();
+            if report.matches.is_empty() {
+                writeln!(out, "  none found").unwrap();
+            }
+            for failure in &report.matches {
+                writeln!(
+                    out,
+                    "\n{}#{}: {} (Score: {:.3})",
+                    failure.repo, failure.pr_number, failure.title, failure.score
+                )
+                .unwrap();
+                writeln!(out, "Failed: {} at {}", failure.check, short_sha(&failure.failed_commit)).unwrap();
+                writeln!(out, "  {}", failure.url).unwrap();
+                for line in failure.signature.lines().take(5) {
+                    writeln!(out, "  | {}", line).unwrap();
+                }
+                writeln!(out, "Fixed by:").unwrap();
+                for commit in &failure.fixing_commits {
+                    writeln!(out, "  - {}", short_commit(commit)).unwrap();
+                }
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "## Similar CI failures\n").unwrap();
+            if report.matches.is_empty() {
+                writeln!(out, "No matching failures found.").unwrap();
+            }
+            for failure in &report.matches {
+                writeln!(out, "### {}#{}: {}\n", failure.repo, failure.pr_number, failure.title).unwrap();
+                writeln!(
+                    out,
+                    "- Failed: [{}]({}) at `{}`",
+                    failure.check,
+                    failure.url,
+                    short_sha(&failure.failed_commit)
+                )
+                .unwrap();
+                writeln!(out, "- Score: {:.3}", failure.score).unwrap();
+                if !failure.fixing_commits.is_empty() {
+                    writeln!(out, "- Fixed by:").unwrap();
+                    for commit in &failure.fixing_commits {
+                        writeln!(out, "  - `{}`", short_commit(commit)).unwrap();
+                    }
+                }
+                let fence = fence(&failure.signature);
+                writeln!(out, "\n{}text\n{}\n{}\n", fence, failure.signature, fence).unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// A code fence longer than any run of backticks in `text`, so that a
+/// diff of a markdown file can't close it early.
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
+
+/// `sha summary` with the SHA abbreviated.
+fn short_commit(commit: &str) -> String {
+    match commit.split_once(' ') {
+        Some((sha, summary)) => format!("{} {}", short_sha(sha), summary),
+        None => short_sha(commit).to_string(),
+    }
+}
+
+pub fn render_pr(format: OutputFormat, pr: &StoredPr) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Json => return Ok(serde_json::to_string_pretty(pr)? + "\n"),
+        OutputFormat::Jsonl => return Ok(serde_json::to_string(pr)? + "\n"),
+        OutputFormat::Text => {
+            writeln!(out, "{}#{}: {}", pr.repo, pr.number, pr.title).unwrap();
+            writeln!(out, "URL: {}", pr.url).unwrap();
+            writeln!(out, "Status: {} (Checks: {})", status_label(&pr.status), pr.checks_status).unwrap();
+            writeln!(out, "\n{}", pr.description).unwrap();
+            if !pr.commits.is_empty() {
+                writeln!(out, "\nCommits:").unwrap();
+                for commit in &pr.commits {
+                    writeln!(out, "  - {}", commit.lines().next().unwrap_or_default()).unwrap();
+                }
+            }
+            if !pr.comments.is_empty() || !pr.review_comments.is_empty() {
+                writeln!(out, "\nDiscussion:").unwrap();
+                for comment in pr.comments.iter().chain(&pr.review_comments) {
+                    writeln!(out, "  - {}", first_line(comment)).unwrap();
+                }
+            }
+            writeln!(out, "\nModified files:").unwrap();
+            for file in &pr.files {
+                writeln!(out, "  - {}", file).unwrap();
+            }
+            writeln!(out, "\n{}", pr.diff).unwrap();
+            if pr.diff_truncated {
+                writeln!(out, "... diff truncated").unwrap();
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "## [{}#{}]({}): {}\n", pr.repo, pr.number, pr.url, pr.title).unwrap();
+            writeln!(out, "- Status: {} (checks: {})\n", status_label(&pr.status), pr.checks_status).unwrap();
+            writeln!(out, "{}\n", pr.description).unwrap();
+            if !pr.commits.is_empty() {
+                writeln!(out, "### Commits\n").unwrap();
+                for commit in &pr.commits {
+                    writeln!(out, "- {}", commit.lines().next().unwrap_or_default()).unwrap();
+                }
+                out.push('\n');
+            }
+            if !pr.comments.is_empty() || !pr.review_comments.is_empty() {
+                writeln!(out, "### Discussion\n").unwrap();
+                for comment in pr.comments.iter().chain(&pr.review_comments) {
+                    writeln!(out, "> {}\n", comment.trim().replace('\n', "\n> ")).unwrap();
+                }
+            }
+            let fence = fence(&pr.diff);
+            writeln!(out, "### Diff\n\n{}diff", fence).unwrap();
+            // A truncated diff can end mid-line; the fence needs a line of its own
+            out.push_str(&pr.diff);
+            if !pr.diff.is_empty() && !pr.diff.ends_with('\n') {
+                out.push('\n');
+            }
+            if pr.diff_truncated {
+                writeln!(out, "... diff truncated").unwrap();
+            }
+            writeln!(out, "{}", fence).unwrap();
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldScore, SearchSnippet};
+    use chrono::{TimeZone, Utc};
+
+    fn result(pr_number: u64, title: &str, status: &str) -> SearchResult {
+        SearchResult {
+            repo: "block/goose".to_string(),
+            pr_number,
+            title: title.to_string(),
+            url: format!("https://github.com/block/goose/pull/{}", pr_number),
+            status: status.to_string(),
+            checks_status: "all_passed".to_string(),
+            files: vec!["crates/goose/src/providers/retry.rs".to_string()],
+            merged_at: (status == "merged").then(|| Utc.with_ymd_and_hms(2024, 5, 1, 15, 30, 0).unwrap()),
+            score: 4.25,
+            bm25_score: 4.25,
+            semantic_score: None,
+            recency_factor: None,
+            field_scores: vec![FieldScore {
+                field: "title".to_string(),
+                boost: 2.0,
+                score: 4.25,
+            }],
+            snippets: vec![SearchSnippet {
+                field: "description".to_string(),
+                fragment: "Retry provider\n requests".to_string(),
+                highlighted: vec![(0, 5)],
+            }],
+            shared_files: Vec::new(),
+        }
+    }
+
+    fn report() -> SearchReport {
+        SearchReport {
+            query: "retry".to_string(),
+            results: vec![result(12, "Retry provider requests", "merged")],
+            unmerged: vec![result(15, "Retry forever", "closed")],
+            hunks: vec![HunkResult {
+                repo: "block/goose".to_string(),
+                pr_number: 12,
+                path: "crates/goose/src/providers/retry.rs".to_string(),
+                old_start: 10,
+                old_lines: 3,
+                new_start: 10,
+                new_lines: 4,
+                header: "@@ -10,3 +10,4 @@ fn retry()".to_string(),
+                score: 1.5,
+            }],
+            review_comments: vec![ReviewCommentResult {
+                repo: "block/goose".to_string(),
+                pr_number: 12,
+                path: "crates/goose/src/providers/retry.rs".to_string(),
+                line: Some(12),
+                body: "Should this come from the config?\nOr the env?".to_string(),
+                url: "https://github.com/block/goose/pull/12#discussion_r1".to_string(),
+                score: 0.5,
+            }],
+        }
+    }
+
+    #[test]
+    fn text_report() {
+        let expected = "
+Searching for PRs matching: retry
+----------------------------------------
+
+block/goose#12: Retry provider requests (Score: 4.250)
+Status: merged (Checks: all_passed)
+Merged: 2024-05-01
+Matched: title 4.250 (x2)
+  [description] **Retry** provider requests
+Modified files:
+  - crates/goose/src/providers/retry.rs
+
+Unmerged PRs (closed without merging or still open):
+----------------------------------------
+
+block/goose#15: Retry forever (Score: 4.250)
+Status: closed without merging (Checks: all_passed)
+Matched: title 4.250 (x2)
+  [description] **Retry** provider requests
+Modified files:
+  - crates/goose/src/providers/retry.rs
+
+Matching code regions:
+  block/goose#12 crates/goose/src/providers/retry.rs:10-13 (Score: 1.500)
+    @@ -10,3 +10,4 @@ fn retry()
+
+Matching review comments:
+  block/goose#12 crates/goose/src/providers/retry.rs:12 (Score: 0.500)
+    Should this come from the config?
+";
+        assert_eq!(render(OutputFormat::Text, &report()).unwrap(), expected);
+    }
+
+    #[test]
+    fn markdown_report() {
+        let expected = "## Similar pull requests
+
+Query: `retry`
+
+### [block/goose#12](https://github.com/block/goose/pull/12): Retry provider requests
+
+- Status: merged (checks: all_passed)
+- Merged: 2024-05-01
+- Score: 4.250
+- Excerpt (description): **Retry** provider requests
+- Files:
+  - `crates/goose/src/providers/retry.rs`
+
+## Unmerged pull requests
+
+Closed without merging or still open; not approaches that landed.
+
+### [block/goose#15](https://github.com/block/goose/pull/15): Retry forever
+
+- Status: closed without merging (checks: all_passed)
+- Score: 4.250
+- Excerpt (description): **Retry** provider requests
+- Files:
+  - `crates/goose/src/providers/retry.rs`
+
+### Matching code regions
+
+- `crates/goose/src/providers/retry.rs:10-13` in block/goose#12 (`@@ -10,3 +10,4 @@ fn retry()`)
+
+### Review discussion
+
+- [block/goose#12](https://github.com/block/goose/pull/12#discussion_r1) on `crates/goose/src/providers/retry.rs:12`: Should this come from the config?
+";
+        assert_eq!(render(OutputFormat::Markdown, &report()).unwrap(), expected);
+    }
+
+    #[test]
+    fn jsonl_report_has_one_result_per_line() {
+        let jsonl = render(OutputFormat::Jsonl, &report()).unwrap();
+        let lines: Vec<_> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"repo":"block/goose","pr_number":12,"title":"Retry provider requests","url":"https://github.com/block/goose/pull/12","status":"merged","checks_status":"all_passed","files":["crates/goose/src/providers/retry.rs"],"merged_at":"2024-05-01T15:30:00Z","score":4.25,"bm25_score":4.25,"semantic_score":null,"field_scores":[{"field":"title","boost":2.0,"score":4.25}],"snippets":[{"field":"description","fragment":"Retry provider\n requests","highlighted":[[0,5]]}]}"#
+        );
+        let unmerged: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!((&unmerged["pr_number"], &unmerged["status"]), (&15.into(), &"closed".into()));
+    }
+
+    #[test]
+    fn json_report() {
+        let json = render(OutputFormat::Json, &report()).unwrap();
+        assert!(json.ends_with("}\n"));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let jsonl = render(OutputFormat::Jsonl, &report()).unwrap();
+        let results: Vec<serde_json::Value> = jsonl.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        assert_eq!(value["query"], "retry");
+        assert_eq!(value["results"], serde_json::json!([results[0]]));
+        assert_eq!(value["unmerged"], serde_json::json!([results[1]]));
+        assert_eq!(
+            value["hunks"],
+            serde_json::json!([{
+                "repo": "block/goose",
+                "pr_number": 12,
+                "path": "crates/goose/src/providers/retry.rs",
+                "old_start": 10,
+                "old_lines": 3,
+                "new_start": 10,
+                "new_lines": 4,
+                "header": "@@ -10,3 +10,4 @@ fn retry()",
+                "score": 1.5
+            }])
+        );
+        assert_eq!(
+            value["review_comments"],
+            serde_json::json!([{
+                "repo": "block/goose",
+                "pr_number": 12,
+                "path": "crates/goose/src/providers/retry.rs",
+                "line": 12,
+                "body": "Should this come from the config?\nOr the env?",
+                "url": "https://github.com/block/goose/pull/12#discussion_r1",
+                "score": 0.5
+            }])
+        );
+    }
+
+    #[test]
+    fn markdown_pr_fences_diffs_of_markdown_files() {
+        let pr = StoredPr {
+            repo: "block/goose".to_string(),
+            number: 3,
+            title: "Document the CLI".to_string(),
+            url: "https://github.com/block/goose/pull/3".to_string(),
+            status: "merged".to_string(),
+            checks_status: "none".to_string(),
+            description: "Usage examples.".to_string(),
+            files: vec!["README.md".to_string()],
+            commits: vec!["Document the CLI\n\nWith examples".to_string()],
+            comments: Vec::new(),
+            review_comments: Vec::new(),
+            diff: "--- a/README.md\n+++ b/README.md\n@@ -1 +1,3 @@\n+```sh\n+goose run\n+```".to_string(),
+            diff_truncated: false,
+        };
+
+        let expected = "## [block/goose#3](https://github.com/block/goose/pull/3): Document the CLI
+
+- Status: merged (checks: none)
+
+Usage examples.
+
+### Commits
+
+- Document the CLI
+
+### Diff
+
+````diff
+--- a/README.md
++++ b/README.md
+@@ -1 +1,3 @@
++```sh
++goose run
++```
+````
+";
+        assert_eq!(render_pr(OutputFormat::Markdown, &pr).unwrap(), expected);
+    }
+
+    #[test]
+    fn fences_outlast_backticks() {
+        assert_eq!(fence("no code here"), "```");
+        assert_eq!(fence("`inline` and ``double``"), "```");
+        assert_eq!(fence("```rust\n```"), "````");
+        assert_eq!(fence("`````"), "``````");
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+/// How many parent directories of each file count towards overlap.
+const DIRECTORY_LEVELS: usize = 3;
+
+/// Weighted features for a set of changed paths: each file counts 1.0 and
+/// each of its parent directories half as much as the level below it, so
+/// PRs touching neighbouring files still overlap, just less.
+pub fn path_features<S: AsRef<str>>(paths: &[S]) -> HashMap<String, f32> {
+    let mut features = HashMap::new();
+
+    for path in paths {
+        let path = path.as_ref().trim().trim_start_matches("./");
+        if path.is_empty() {
+            continue;
+        }
+        bump(&mut features, path.to_string(), 1.0);
+
+        let mut weight = 1.0;
+        let mut dir = path;
+        for _ in 0..DIRECTORY_LEVELS {
+            let Some((parent, _)) = dir.rsplit_once('/') else {
+                break;
+            };
+            weight /= 2.0;
+            bump(&mut features, format!("{}/", parent), weight);
+            dir = parent;
+        }
+    }
+
+    features
+}
+
+fn bump(features: &mut HashMap<String, f32>, key: String, weight: f32) {
+    let entry = features.entry(key).or_insert(0.0);
+    *entry = entry.max(weight);
+}
+
+/// Weighted Jaccard similarity: sum of per-feature minimums over sum of maximums.
+pub fn weighted_jaccard(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let mut min_sum = 0.0;
+    let mut max_sum = 0.0;
+
+    for (key, &wa) in a {
+        let wb = b.get(key).copied().unwrap_or(0.0);
+        min_sum += wa.min(wb);
+        max_sum += wa.max(wb);
+    }
+    for (key, &wb) in b {
+        if !a.contains_key(key) {
+            max_sum += wb;
+        }
+    }
+
+    if max_sum > 0.0 {
+        min_sum / max_sum
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_features_weight_parent_directories() {
+        let features = path_features(&["./crates/goose/src/providers/openai.rs", ""]);
+        let mut features: Vec<_> = features.into_iter().collect();
+        features.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert_eq!(
+            features,
+            [
+                ("crates/goose/src/providers/openai.rs".to_string(), 1.0),
+                ("crates/goose/src/providers/".to_string(), 0.5),
+                ("crates/goose/src/".to_string(), 0.25),
+                ("crates/goose/".to_string(), 0.125),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_features_keep_the_heaviest_weight() {
+        // `src/` is both a file's parent and a grandparent
+        let features = path_features(&["src/main.rs", "src/cli/args.rs"]);
+        assert_eq!(features["src/"], 0.5);
+        assert_eq!(features["src/cli/"], 0.5);
+    }
+
+    #[test]
+    fn weighted_jaccard_of_identical_and_disjoint_sets() {
+        let a = path_features(&["src/main.rs"]);
+        assert_eq!(weighted_jaccard(&a, &a), 1.0);
+        assert_eq!(weighted_jaccard(&a, &path_features(&["docs/index.md"])), 0.0);
+        assert_eq!(weighted_jaccard(&HashMap::new(), &HashMap::new()), 0.0);
+    }
+
+    #[test]
+    fn weighted_jaccard_credits_neighbouring_files() {
+        let a = path_features(&["src/providers/openai.rs"]);
+        let sibling = path_features(&["src/providers/anthropic.rs"]);
+        let elsewhere = path_features(&["src/cli/args.rs"]);
+
+        // Shared: providers/ 0.5 and src/ 0.25, out of 1 + 1 + 0.5 + 0.25
+        assert!((weighted_jaccard(&a, &sibling) - 0.75 / 2.75).abs() < 1e-6);
+        assert!(weighted_jaccard(&a, &sibling) > weighted_jaccard(&a, &elsewhere));
+        assert_eq!(weighted_jaccard(&a, &sibling), weighted_jaccard(&sibling, &a));
+    }
+}
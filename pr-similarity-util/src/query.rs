@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+use crate::github::IssueDetails;
+
+/// Upper bound on terms in a generated query; long issue bodies otherwise
+/// drown the title in template boilerplate.
+const MAX_QUERY_TERMS: usize = 48;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does",
+    "doing", "for", "from", "get", "got", "had", "has", "have", "having", "how", "i", "if", "in",
+    "into", "is", "it", "its", "just", "me", "my", "no", "not", "of", "on", "or", "other", "our",
+    "out", "please", "should", "so", "some", "such", "than", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "those", "to", "up", "us", "was", "we", "were",
+    "what", "when", "where", "which", "while", "who", "why", "will", "with", "would", "you",
+    "your",
+];
+
+/// Labels that say nothing about which part of the code an issue concerns.
+const GENERIC_LABELS: &[&str] = &[
+    "bug",
+    "enhancement",
+    "feature",
+    "good first issue",
+    "help wanted",
+    "question",
+    "duplicate",
+    "invalid",
+    "wontfix",
+    "triage",
+];
+
+/// Turn an issue into a plain keyword query: title terms first, then
+/// labels, then the body, with markup, URLs and stop words removed.
+pub fn issue_query(issue: &IssueDetails) -> String {
+    let labels: Vec<&str> = issue
+        .labels
+        .iter()
+        .map(String::as_str)
+        .filter(|label| !GENERIC_LABELS.contains(&label.to_lowercase().as_str()))
+        .collect();
+
+    let text = format!("{}\n{}\n{}", issue.title, labels.join(" "), issue.body);
+    clean_query(&text)
+}
+
+/// Reduce arbitrary text to space-separated keywords that `QueryParser`
+/// accepts as plain terms.
+pub fn clean_query(text: &str) -> String {
+    let text = strip_html_comments(text);
+    let mut seen = HashSet::new();
+    let mut terms = Vec::new();
+
+    for word in text.split_whitespace() {
+        if word.starts_with("http://") || word.starts_with("https://") || word.contains("](http") {
+            continue;
+        }
+
+        let cleaned: String = word
+            .chars()
+            .map(|c| if c.is_alphanumeric() || matches!(c, '_' | '.' | '/' | '-') { c } else { ' ' })
+            .collect();
+
+        for term in cleaned.split_whitespace() {
+            let term = term.trim_matches(|c: char| !c.is_alphanumeric());
+            if term.len() < 2 || STOP_WORDS.contains(&term.to_lowercase().as_str()) {
+                continue;
+            }
+            if seen.insert(term.to_lowercase()) {
+                terms.push(term.to_string());
+            }
+            if terms.len() == MAX_QUERY_TERMS {
+                return terms.join(" ");
+            }
+        }
+    }
+
+    terms.join(" ")
+}
+
+fn strip_html_comments(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("-->") {
+            Some(end) => rest = &rest[start + end + 3..],
+            None => return out,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse `--issue` input: an issue URL such as
+/// `https://github.com/block/goose/issues/1022`, or a bare number.
+/// Returns the `owner/repo` named in the URL, if any, and the issue number.
+/// Query strings and fragments, as in links to a comment, are ignored.
+pub fn parse_issue_ref(input: &str) -> Result<(Option<String>, u64)> {
+    let input = input.trim().trim_start_matches('#');
+    if let Ok(number) = input.parse() {
+        return Ok((None, number));
+    }
+
+    let path = input.split_once("://").map_or(input, |(_, rest)| rest);
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    match path.trim_end_matches('/').split('/').collect::<Vec<_>>().as_slice() {
+        [_host, owner, repo, "issues", number] => {
+            let number = number
+                .parse()
+                .map_err(|_| anyhow!("invalid issue number in '{}'", input))?;
+            Ok((Some(format!("{}/{}", owner, repo)), number))
+        }
+        _ => Err(anyhow!("expected an issue URL or number, got '{}'", input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_issue_ref_accepts_numbers() {
+        assert_eq!(parse_issue_ref("1022").unwrap(), (None, 1022));
+        assert_eq!(parse_issue_ref(" #1022 ").unwrap(), (None, 1022));
+    }
+
+    #[test]
+    fn parse_issue_ref_accepts_urls() {
+        let goose = Some("block/goose".to_string());
+        for url in [
+            "https://github.com/block/goose/issues/1022",
+            "https://github.com/block/goose/issues/1022/",
+            "github.com/block/goose/issues/1022",
+            "https://github.com/block/goose/issues/1022#issuecomment-2571234567",
+            "https://github.com/block/goose/issues/1022?notification_referrer_id=NT_abc",
+            "https://github.com/block/goose/issues/1022/?q=is%3Aopen#top",
+        ] {
+            assert_eq!(parse_issue_ref(url).unwrap(), (goose.clone(), 1022), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_issue_ref_rejects_other_urls() {
+        assert!(parse_issue_ref("https://github.com/block/goose/pull/804").is_err());
+        assert!(parse_issue_ref("https://github.com/block/goose/issues/new").is_err());
+        assert!(parse_issue_ref("goose").is_err());
+    }
+
+    #[test]
+    fn clean_query_keeps_identifiers_and_drops_noise() {
+        let text = "<!-- template: describe the bug -->\n\
+                    The `ProviderConfig` is not loaded from ~/.config/goose/config.yaml \
+                    when I run goose-cli. See https://example.com/logs and [docs](https://example.com).";
+        assert_eq!(
+            clean_query(text),
+            "ProviderConfig loaded config/goose/config.yaml run goose-cli See"
+        );
+    }
+
+    #[test]
+    fn clean_query_drops_repeats_and_caps_terms() {
+        assert_eq!(clean_query("Retry retry RETRY the request"), "Retry request");
+        let long: String = (0..100).map(|i| format!("term{} ", i)).collect();
+        assert_eq!(clean_query(&long).split(' ').count(), MAX_QUERY_TERMS);
+    }
+}
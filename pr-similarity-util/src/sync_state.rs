@@ -0,0 +1,139 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const SYNC_STATE_FILE: &str = "sync_state.json";
+
+/// Syncs in which a PR may fail to fetch before it is given up on. Until
+/// then the watermark stays behind it so that it is retried; a PR that
+/// can never be fetched (deleted, or with a diff too large to serve) would
+/// otherwise make every sync fetch everything merged after it again.
+pub const MAX_FETCH_ATTEMPTS: u32 = 3;
+
+/// Per-repository sync watermarks, persisted next to the Tantivy index.
+///
+/// The watermark is the most recent `merged_at` timestamp that has been
+/// indexed for a repository, so the next sync only needs to fetch PRs
+/// merged after it. Unmerged PRs, when they are synced, have a watermark
+/// of their own: the most recent `updated_at` indexed. PRs that failed to
+/// fetch are remembered along with the number of syncs they failed in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    watermarks: HashMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    unmerged_watermarks: HashMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    failures: HashMap<String, HashMap<u64, u32>>,
+}
+
+impl SyncState {
+    pub fn load(cache_dir: &Path) -> Result<Self> {
+        let path = Self::path(cache_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(Self::path(cache_dir), contents)?;
+        Ok(())
+    }
+
+    pub fn watermark(&self, repo: &str) -> Option<DateTime<Utc>> {
+        self.watermarks.get(repo).copied()
+    }
+
+    /// Advance the watermark for `repo`, never moving it backwards.
+    pub fn advance(&mut self, repo: &str, merged_at: DateTime<Utc>) {
+        advance(&mut self.watermarks, repo, merged_at);
+    }
+
+    pub fn unmerged_watermark(&self, repo: &str) -> Option<DateTime<Utc>> {
+        self.unmerged_watermarks.get(repo).copied()
+    }
+
+    /// Advance the unmerged-PR watermark for `repo`, never moving it backwards.
+    pub fn advance_unmerged(&mut self, repo: &str, updated_at: DateTime<Utc>) {
+        advance(&mut self.unmerged_watermarks, repo, updated_at);
+    }
+
+    /// Note that PR `number` failed to fetch, returning the number of syncs
+    /// it has failed in so far.
+    pub fn record_failure(&mut self, repo: &str, number: u64) -> u32 {
+        let attempts = self.failures.entry(repo.to_string()).or_default().entry(number).or_insert(0);
+        *attempts += 1;
+        *attempts
+    }
+
+    /// Forget past failures of PR `number`, once it has been fetched.
+    pub fn clear_failure(&mut self, repo: &str, number: u64) {
+        if let Some(failures) = self.failures.get_mut(repo) {
+            failures.remove(&number);
+            if failures.is_empty() {
+                self.failures.remove(repo);
+            }
+        }
+    }
+
+    /// Number of syncs in which PR `number` failed to fetch since it was
+    /// last fetched.
+    pub fn failed_attempts(&self, repo: &str, number: u64) -> u32 {
+        self.failures
+            .get(repo)
+            .and_then(|failures| failures.get(&number))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn clear(&mut self, repo: &str) {
+        self.watermarks.remove(repo);
+        self.unmerged_watermarks.remove(repo);
+        self.failures.remove(repo);
+    }
+
+    fn path(cache_dir: &Path) -> PathBuf {
+        cache_dir.join(SYNC_STATE_FILE)
+    }
+}
+
+fn advance(watermarks: &mut HashMap<String, DateTime<Utc>>, repo: &str, to: DateTime<Utc>) {
+    let entry = watermarks.entry(repo.to_string()).or_insert(to);
+    if to > *entry {
+        *entry = to;
+    }
+}
+
+/// How `load_recent_prs` fetches and commits PRs.
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    /// Fetches (single PRs or GraphQL batches) run at the same time.
+    pub concurrency: usize,
+    /// Commit the index after this many fetched PRs, so an interrupted
+    /// sync keeps what it already has.
+    pub commit_every: usize,
+    /// PRs fetched per GraphQL query; 0 fetches each PR over REST instead.
+    pub graphql_batch_size: usize,
+    /// Also look for CI failures that each PR fixed before merging and
+    /// index their log excerpts. Costs extra requests per PR.
+    pub index_failures: bool,
+    /// Index closed-unmerged and open PRs too, not just merged ones.
+    pub include_unmerged: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            commit_every: 25,
+            graphql_batch_size: 10,
+            index_failures: false,
+            include_unmerged: false,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use tantivy::tokenizer::{
+    LowerCaser, RemoveLongFilter, TextAnalyzer, Token, TokenStream, Tokenizer, TokenizerManager,
+};
+
+/// Name the code tokenizer is registered under in the index.
+pub const CODE_TOKENIZER: &str = "code";
+
+/// Build the analyzer used to index code-ish fields (`title`, `files`, `diff`).
+pub fn code_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(CodeTokenizer { keep_whole: true })
+        .filter(RemoveLongFilter::limit(80))
+        .filter(LowerCaser)
+        .build()
+}
+
+/// Tokenizers for parsing queries against the index.
+///
+/// Queries are split into parts only: a query for `provider_config` becomes
+/// the phrase "provider config", which matches `ProviderConfig` as well.
+/// Keeping the whole token would require it verbatim in every match.
+pub fn query_tokenizers() -> TokenizerManager {
+    let manager = TokenizerManager::default();
+    manager.register(
+        CODE_TOKENIZER,
+        TextAnalyzer::builder(CodeTokenizer { keep_whole: false })
+            .filter(RemoveLongFilter::limit(80))
+            .filter(LowerCaser)
+            .build(),
+    );
+    manager
+}
+
+/// Lowercased word parts of `text`, as the query tokenizer sees them.
+pub fn code_terms(text: &str) -> Vec<String> {
+    tokenize(text, false)
+        .into_iter()
+        .map(|token| token.text.to_lowercase())
+        .collect()
+}
+
+/// Splits identifiers and paths into their parts while also keeping the
+/// whole token, so `ProviderConfig`, `provider_config.rs` and
+/// `crates/goose/src/providers/` all match a query for "provider config".
+///
+/// A "word" is a run of alphanumerics joined by `_`, `-`, `.`, `/` or `:`.
+/// Each word is emitted as its parts, split on those separators and on
+/// camelCase boundaries, preceded by the whole word when `keep_whole` is set.
+/// The whole token shares the position of its first part so phrase queries
+/// over the parts still work.
+#[derive(Clone)]
+pub struct CodeTokenizer {
+    /// Also emit each multi-part word as a single token.
+    pub keep_whole: bool,
+}
+
+pub struct CodeTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Tokenizer for CodeTokenizer {
+    type TokenStream<'a> = CodeTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CodeTokenStream {
+        CodeTokenStream {
+            tokens: tokenize(text, self.keep_whole),
+            index: 0,
+        }
+    }
+}
+
+impl TokenStream for CodeTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+fn is_joiner(c: char) -> bool {
+    matches!(c, '_' | '-' | '.' | '/' | ':')
+}
+
+fn tokenize(text: &str, keep_whole: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    for (start, end) in words(text) {
+        let parts = split_parts(text, start, end);
+        if keep_whole && parts.len() > 1 {
+            tokens.push(make_token(text, start, end, position));
+        }
+        for (part_start, part_end) in parts {
+            tokens.push(make_token(text, part_start, part_end, position));
+            position += 1;
+        }
+    }
+
+    tokens
+}
+
+fn make_token(text: &str, offset_from: usize, offset_to: usize, position: usize) -> Token {
+    Token {
+        offset_from,
+        offset_to,
+        position,
+        text: text[offset_from..offset_to].to_string(),
+        position_length: 1,
+    }
+}
+
+/// Byte ranges of words: alphanumeric runs, possibly joined by separators,
+/// with no leading or trailing separator.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut last_alnum_end = 0;
+
+    for (offset, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            start.get_or_insert(offset);
+            last_alnum_end = offset + c.len_utf8();
+        } else if !is_joiner(c) {
+            if let Some(word_start) = start.take() {
+                words.push((word_start, last_alnum_end));
+            }
+        }
+    }
+    if let Some(word_start) = start {
+        words.push((word_start, last_alnum_end));
+    }
+
+    words
+}
+
+/// Split a word on separators and case changes: `HTTPServerConfig` becomes
+/// `HTTP`, `Server`, `Config`.
+fn split_parts(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut parts = Vec::new();
+    let mut part_start: Option<usize> = None;
+    let chars: Vec<(usize, char)> = text[start..end]
+        .char_indices()
+        .map(|(offset, c)| (start + offset, c))
+        .collect();
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(s) = part_start.take() {
+                parts.push((s, offset));
+            }
+            continue;
+        }
+
+        if let Some(s) = part_start {
+            let prev = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|&(_, c)| c);
+            let lower_to_upper = prev.is_lowercase() && c.is_uppercase();
+            let acronym_end = prev.is_uppercase()
+                && c.is_uppercase()
+                && next.is_some_and(|n| n.is_lowercase());
+            if lower_to_upper || acronym_end {
+                parts.push((s, offset));
+                part_start = Some(offset);
+            }
+        } else {
+            part_start = Some(offset);
+        }
+    }
+    if let Some(s) = part_start {
+        parts.push((s, end));
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (text, position) of every token the analyzer emits.
+    fn analyze(mut analyzer: TextAnalyzer, text: &str) -> Vec<(String, usize)> {
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push((stream.token().text.clone(), stream.token().position));
+        }
+        tokens
+    }
+
+    fn tokens(list: &[(&str, usize)]) -> Vec<(String, usize)> {
+        list.iter().map(|&(text, position)| (text.to_string(), position)).collect()
+    }
+
+    #[test]
+    fn camel_case_keeps_whole_token_at_first_position() {
+        assert_eq!(
+            analyze(code_analyzer(), "ProviderConfig"),
+            tokens(&[("providerconfig", 0), ("provider", 0), ("config", 1)])
+        );
+    }
+
+    #[test]
+    fn paths_and_snake_case_split_on_separators() {
+        assert_eq!(
+            analyze(code_analyzer(), "open src/provider_config.rs"),
+            tokens(&[
+                ("open", 0),
+                ("src/provider_config.rs", 1),
+                ("src", 1),
+                ("provider", 2),
+                ("config", 3),
+                ("rs", 4),
+            ])
+        );
+    }
+
+    #[test]
+    fn acronyms_split_before_the_next_word() {
+        assert_eq!(code_terms("HTTPServerConfig parseJSON"), ["http", "server", "config", "parse", "json"]);
+    }
+
+    #[test]
+    fn query_tokenizer_emits_parts_only() {
+        let analyzer = query_tokenizers().get(CODE_TOKENIZER).unwrap();
+        assert_eq!(analyze(analyzer, "ProviderConfig"), tokens(&[("provider", 0), ("config", 1)]));
+    }
+
+    #[test]
+    fn words_drop_leading_and_trailing_separators() {
+        assert_eq!(code_terms("--force-refresh, ./main.rs:: `x`"), ["force", "refresh", "main", "rs", "x"]);
+        assert_eq!(analyze(code_analyzer(), "(plain)"), tokens(&[("plain", 0)]));
+    }
+
+    #[test]
+    fn offsets_point_into_multibyte_text() {
+        let text = "größeFactor";
+        let parts: Vec<_> = tokenize(text, false)
+            .iter()
+            .map(|t| &text[t.offset_from..t.offset_to])
+            .collect();
+        assert_eq!(parts, ["größe", "Factor"]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Just enough of the zip format to read GitHub Actions log archives:
+//! stored and deflated entries, no zip64, no encryption.
+
+use anyhow::{anyhow, Result};
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// One file in an archive.
+#[derive(Debug)]
+pub struct ZipEntry {
+    /// Path inside the archive, with `/` separators.
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Read every file in a zip archive, in central directory order.
+/// Directory entries are skipped.
+pub fn read_zip(data: &[u8]) -> Result<Vec<ZipEntry>> {
+    let end = find_end_of_central_directory(data)?;
+    let entry_count = u16_at(data, end + 10)? as usize;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        if u32_at(data, offset)? != CENTRAL_DIRECTORY_HEADER {
+            return Err(anyhow!("corrupt zip: bad central directory entry at {}", offset));
+        }
+        let method = u16_at(data, offset + 10)?;
+        let compressed_size = u32_at(data, offset + 20)?;
+        let uncompressed_size = u32_at(data, offset + 24)?;
+        let name_len = u16_at(data, offset + 28)? as usize;
+        let extra_len = u16_at(data, offset + 30)? as usize;
+        let comment_len = u16_at(data, offset + 32)? as usize;
+        let local_offset = u32_at(data, offset + 42)?;
+        let name = String::from_utf8_lossy(slice(data, offset + 46, name_len)?).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if [compressed_size, uncompressed_size, local_offset].contains(&u32::MAX) {
+            return Err(anyhow!("zip64 archives are not supported ({})", name));
+        }
+        if name.ends_with('/') {
+            continue;
+        }
+
+        // Sizes come from the central directory, since local headers may
+        // defer them to a data descriptor
+        let local_offset = local_offset as usize;
+        if u32_at(data, local_offset)? != LOCAL_FILE_HEADER {
+            return Err(anyhow!("corrupt zip: bad local header for {}", name));
+        }
+        let local_name_len = u16_at(data, local_offset + 26)? as usize;
+        let local_extra_len = u16_at(data, local_offset + 28)? as usize;
+        let data_start = local_offset + 30 + local_name_len + local_extra_len;
+        let raw = slice(data, data_start, compressed_size as usize)?;
+        let contents = match method {
+            STORED => raw.to_vec(),
+            DEFLATED => miniz_oxide::inflate::decompress_to_vec(raw)
+                .map_err(|e| anyhow!("failed to inflate {}: {}", name, e))?,
+            other => return Err(anyhow!("unsupported compression method {} for {}", other, name)),
+        };
+        entries.push(ZipEntry { name, contents });
+    }
+    Ok(entries)
+}
+
+/// The end-of-central-directory record sits at the end of the archive,
+/// followed only by an optional comment of up to 64 KiB.
+fn find_end_of_central_directory(data: &[u8]) -> Result<usize> {
+    let earliest = data.len().saturating_sub(22 + u16::MAX as usize);
+    (earliest..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(data, i).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| anyhow!("not a zip archive"))
+}
+
+fn slice(data: &[u8], start: usize, len: usize) -> Result<&[u8]> {
+    data.get(start..start + len)
+        .ok_or_else(|| anyhow!("corrupt zip: truncated at {}", start))
+}
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(slice(data, at, 2)?.try_into().unwrap()))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(slice(data, at, 4)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zip archive holding `files` as `(name, method, contents)`, laid out
+    /// the way `zip` and GitHub write them.
+    fn archive(files: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for &(name, method, contents) in files {
+            let compressed = match method {
+                DEFLATED => miniz_oxide::deflate::compress_to_vec(contents, 6),
+                _ => contents.to_vec(),
+            };
+            let sizes = [(compressed.len() as u32).to_le_bytes(), (contents.len() as u32).to_le_bytes()].concat();
+            let local_offset = data.len() as u32;
+
+            data.extend(LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend([20, 0, 0, 0]); // version, flags
+            data.extend(method.to_le_bytes());
+            data.extend([0; 8]); // time, date, crc
+            data.extend(&sizes);
+            data.extend((name.len() as u16).to_le_bytes());
+            data.extend([0, 0]); // extra length
+            data.extend(name.as_bytes());
+            data.extend(&compressed);
+
+            central.extend(CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+            central.extend([20, 0, 20, 0, 0, 0]); // versions, flags
+            central.extend(method.to_le_bytes());
+            central.extend([0; 8]); // time, date, crc
+            central.extend(&sizes);
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0; 12]); // extra and comment lengths, disk, attributes
+            central.extend(local_offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+
+        let central_offset = data.len() as u32;
+        let count = (files.len() as u16).to_le_bytes();
+        data.extend(&central);
+        data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend([0; 4]); // disk numbers
+        data.extend(count);
+        data.extend(count);
+        data.extend((central.len() as u32).to_le_bytes());
+        data.extend(central_offset.to_le_bytes());
+        data.extend([0, 0]); // comment length
+        data
+    }
+
+    fn logs() -> Vec<u8> {
+        archive(&[
+            ("1_build.txt", DEFLATED, b"Compiling widgets\nerror[E0308]: mismatched types\n".as_slice()),
+            ("build/", STORED, b"".as_slice()),
+            ("build/1_Set up job.txt", STORED, b"Runner ready\n".as_slice()),
+        ])
+    }
+
+    /// Offset of the first central directory entry.
+    fn central_directory(data: &[u8]) -> usize {
+        let end = find_end_of_central_directory(data).unwrap();
+        u32_at(data, end + 16).unwrap() as usize
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let entries = read_zip(&logs()).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["1_build.txt", "build/1_Set up job.txt"]);
+        assert_eq!(entries[0].contents, b"Compiling widgets\nerror[E0308]: mismatched types\n");
+        assert_eq!(entries[1].contents, b"Runner ready\n");
+    }
+
+    #[test]
+    fn finds_the_end_record_behind_a_comment() {
+        let mut data = logs();
+        let comment = b"generated by the runner";
+        let comment_len = data.len() - 2;
+        data[comment_len..].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        data.extend(comment);
+        assert_eq!(read_zip(&data).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_data_without_an_end_record() {
+        let error = read_zip(b"PK\x03\x04 not really a zip").unwrap_err();
+        assert_eq!(error.to_string(), "not a zip archive");
+        assert!(read_zip(&[]).is_err());
+
+        let data = logs();
+        let error = read_zip(&data[..data.len() - 1]).unwrap_err();
+        assert_eq!(error.to_string(), "not a zip archive");
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_archives() {
+        let data = logs();
+        let central = central_directory(&data);
+
+        // Entry data cut off before its end
+        let mut truncated = data.clone();
+        truncated[central + 20..central + 24].copy_from_slice(&10_000u32.to_le_bytes());
+        let error = read_zip(&truncated).unwrap_err();
+        assert!(error.to_string().starts_with("corrupt zip: truncated"), "{}", error);
+
+        let mut bad_local = data.clone();
+        bad_local[0] = 0;
+        assert_eq!(read_zip(&bad_local).unwrap_err().to_string(), "corrupt zip: bad local header for 1_build.txt");
+
+        let mut bad_central = data.clone();
+        bad_central[central] = 0;
+        let error = read_zip(&bad_central).unwrap_err();
+        assert!(error.to_string().starts_with("corrupt zip: bad central directory entry"), "{}", error);
+
+        let mut bad_deflate = data;
+        bad_deflate[30 + "1_build.txt".len()] = 0xff;
+        let error = read_zip(&bad_deflate).unwrap_err();
+        assert!(error.to_string().starts_with("failed to inflate 1_build.txt"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_zip64_and_unknown_methods() {
+        let data = logs();
+        let central = central_directory(&data);
+
+        let mut zip64 = data.clone();
+        zip64[central + 20..central + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = read_zip(&zip64).unwrap_err();
+        assert_eq!(error.to_string(), "zip64 archives are not supported (1_build.txt)");
+
+        let bzip2 = archive(&[("1_build.txt", 12, b"BZh".as_slice())]);
+        let error = read_zip(&bzip2).unwrap_err();
+        assert_eq!(error.to_string(), "unsupported compression method 12 for 1_build.txt");
+    }
+}
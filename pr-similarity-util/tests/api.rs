@@ -0,0 +1,289 @@
+mod support;
+
+use pr_similarity_search::api::{ApiClient, RetryPolicy};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use support::{temp_dir, MockServer, Response};
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 3,
+        base_delay: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        secondary_limit_wait: Duration::from_millis(10),
+        max_wait: Duration::from_secs(60),
+    }
+}
+
+fn client(server: &MockServer) -> ApiClient {
+    ApiClient::new("test-token".to_string(), &server.url)
+        .unwrap()
+        .with_retry_policy(fast_retries())
+}
+
+/// Serve `first` for the first `times` requests, then `{"ok": true}`.
+async fn failing_then_ok(first: Response, times: usize) -> MockServer {
+    let calls = AtomicUsize::new(0);
+    MockServer::start(move |_| {
+        if calls.fetch_add(1, Ordering::SeqCst) < times {
+            first.clone()
+        } else {
+            Response::ok(r#"{"ok": true}"#)
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn sends_token_and_accept_header() {
+    let server = MockServer::start(|_| Response::ok("[]")).await;
+
+    let _: Vec<Value> = client(&server)
+        .get_json("/repos/o/r/pulls", &[("page", "2".to_string())])
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/repos/o/r/pulls?page=2");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer test-token"));
+    assert_eq!(requests[0].header("accept"), Some("application/vnd.github+json"));
+}
+
+#[tokio::test]
+async fn retries_secondary_rate_limit_after_retry_after() {
+    let limited = Response::status(403)
+        .header("Retry-After", "1")
+        .body(r#"{"message": "You have exceeded a secondary rate limit."}"#);
+    let server = failing_then_ok(limited, 1).await;
+
+    let start = Instant::now();
+    let body: Value = client(&server).get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+
+    assert_eq!(body["ok"], true);
+    assert_eq!(server.requests().len(), 2);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn retries_too_many_requests() {
+    let server = failing_then_ok(Response::status(429), 2).await;
+
+    let body: Value = client(&server).get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+
+    assert_eq!(body["ok"], true);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn waits_for_primary_rate_limit_reset() {
+    let reset = chrono::Utc::now().timestamp();
+    let limited = Response::status(403)
+        .header("X-RateLimit-Remaining", "0")
+        .header("X-RateLimit-Reset", reset.to_string())
+        .body(r#"{"message": "API rate limit exceeded"}"#);
+    let server = failing_then_ok(limited, 1).await;
+
+    let start = Instant::now();
+    let body: Value = client(&server).get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+
+    assert_eq!(body["ok"], true);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn fails_fast_when_reset_is_too_far_away() {
+    let reset = chrono::Utc::now().timestamp() + 3600;
+    let limited = Response::status(403)
+        .header("X-RateLimit-Remaining", "0")
+        .header("X-RateLimit-Reset", reset.to_string());
+    let server = failing_then_ok(limited, 1).await;
+
+    let error = client(&server)
+        .get_json::<Value>("/repos/o/r/pulls/1", &[])
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("rate limit"), "{}", error);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn does_not_retry_forbidden_without_rate_limit() {
+    let forbidden = Response::status(403).body(r#"{"message": "Resource not accessible by integration"}"#);
+    let server = failing_then_ok(forbidden, 1).await;
+
+    let error = client(&server)
+        .get_json::<Value>("/repos/o/r/pulls/1", &[])
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("Resource not accessible"), "{}", error);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn retries_server_errors_with_backoff() {
+    let server = failing_then_ok(Response::status(502), 2).await;
+
+    let body: Value = client(&server).get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+
+    assert_eq!(body["ok"], true);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let server = MockServer::start(|_| Response::status(500)).await;
+
+    let result = client(&server).get_json::<Value>("/repos/o/r/pulls/1", &[]).await;
+
+    assert!(result.is_err());
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    let policy = fast_retries();
+    let delays: Vec<_> = [0, 1, 2, 3, 40, u32::MAX].map(|attempt| policy.backoff_delay(attempt).as_millis()).to_vec();
+
+    assert_eq!(delays, [10, 20, 40, 40, 40, 40]);
+
+    let slow = RetryPolicy {
+        base_delay: Duration::MAX,
+        ..RetryPolicy::default()
+    };
+    assert_eq!(slow.backoff_delay(1), slow.max_backoff);
+}
+
+#[tokio::test]
+async fn revalidates_cached_responses_with_etag() {
+    let server = MockServer::start(|request| match request.header("if-none-match") {
+        Some("\"v1\"") => Response::status(304),
+        _ => Response::ok(r#"{"title": "cached"}"#).header("ETag", "\"v1\""),
+    })
+    .await;
+    let cache = temp_dir("etag-cache");
+
+    for _ in 0..2 {
+        let client = client(&server).with_response_cache(cache.clone());
+        let body: Value = client.get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+        assert_eq!(body["title"], "cached");
+    }
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("if-none-match"), None);
+    assert_eq!(requests[1].header("if-none-match"), Some("\"v1\""));
+    std::fs::remove_dir_all(cache).unwrap();
+}
+
+#[tokio::test]
+async fn cache_is_keyed_by_media_type() {
+    let server = MockServer::start(|request| match request.header("accept") {
+        Some("application/vnd.github.v3.diff") => Response::ok("diff --git a/x b/x\n").header("ETag", "\"d\""),
+        _ => Response::ok("{}").header("ETag", "\"j\""),
+    })
+    .await;
+    let cache = temp_dir("media-type-cache");
+    let client = client(&server).with_response_cache(cache.clone());
+
+    let _: Value = client.get_json("/repos/o/r/pulls/1", &[]).await.unwrap();
+    let diff = client
+        .get_text("/repos/o/r/pulls/1", "application/vnd.github.v3.diff")
+        .await
+        .unwrap();
+
+    assert!(diff.starts_with("diff --git"));
+    assert_eq!(server.requests()[1].header("if-none-match"), None);
+    std::fs::remove_dir_all(cache).unwrap();
+}
+
+#[tokio::test]
+async fn cache_evicts_least_recently_used_responses() {
+    let server = MockServer::start(|request| {
+        let etag = format!("\"{}\"", request.path.rsplit('/').next().unwrap());
+        if request.header("if-none-match") == Some(etag.as_str()) {
+            Response::status(304)
+        } else {
+            Response::ok(format!(r#"{{"body": "{}"}}"#, "x".repeat(2000))).header("ETag", etag)
+        }
+    })
+    .await;
+    let cache = temp_dir("lru-cache");
+    let client = client(&server).with_response_cache_limit(cache.clone(), 6000);
+
+    for pr in [1, 2, 1, 3, 1, 2] {
+        let _: Value = client.get_json(&format!("/repos/o/r/pulls/{}", pr), &[]).await.unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // Storing #3 went over the limit and evicted #2, which had been used
+    // less recently than #1
+    let revalidated: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request.header("if-none-match").is_some())
+        .collect();
+    assert_eq!(revalidated, [false, false, true, false, true, false]);
+    assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 2);
+    std::fs::remove_dir_all(cache).unwrap();
+}
+
+#[tokio::test]
+async fn graphql_posts_query_and_retries_rate_limit() {
+    let calls = AtomicUsize::new(0);
+    let server = MockServer::start(move |_| {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            Response::ok(r#"{"errors": [{"type": "RATE_LIMITED", "message": "API rate limit exceeded"}]}"#)
+        } else {
+            Response::ok(r#"{"data": {"viewer": {"login": "octocat"}}}"#)
+        }
+    })
+    .await;
+
+    let data: Value = client(&server)
+        .graphql("query { viewer { login } }", serde_json::json!({"n": 1}))
+        .await
+        .unwrap();
+
+    assert_eq!(data["viewer"]["login"], "octocat");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/graphql");
+    let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["query"], "query { viewer { login } }");
+    assert_eq!(body["variables"]["n"], 1);
+}
+
+#[tokio::test]
+async fn graphql_keeps_partial_data() {
+    let server = MockServer::start(|_| {
+        Response::ok(
+            r#"{"data": {"repository": {"pr0": null}},
+                "errors": [{"type": "NOT_FOUND", "message": "Could not resolve to a PullRequest"}]}"#,
+        )
+    })
+    .await;
+
+    let data: Value = client(&server)
+        .graphql("query { repository { pr0: pullRequest(number: 1) { title } } }", serde_json::json!({}))
+        .await
+        .unwrap();
+
+    assert!(data["repository"]["pr0"].is_null());
+}
+
+#[tokio::test]
+async fn graphql_fails_without_data() {
+    let server = MockServer::start(|_| Response::ok(r#"{"errors": [{"message": "Parse error"}]}"#)).await;
+
+    let error = client(&server)
+        .graphql::<Value>("query {", serde_json::json!({}))
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("Parse error"), "{}", error);
+}
@@ -0,0 +1,236 @@
+mod support;
+
+use pr_similarity_search::api::{api_base_url, ApiClient, DEFAULT_API_URL};
+use pr_similarity_search::ci_failure::{find_ci_failures, resolved_failures};
+use pr_similarity_search::github::{CheckSource, CommitInfo, GitHubClient, PullRequestDetails};
+use support::{MockServer, Request, Response};
+
+const PULL_REQUEST: &str = include_str!("fixtures/ghes/pull_request.json");
+const COMMITS: &str = include_str!("fixtures/ghes/commits.json");
+const ISSUE_COMMENTS: &str = include_str!("fixtures/ghes/issue_comments.json");
+const REVIEWS: &str = include_str!("fixtures/ghes/reviews.json");
+const REVIEW_COMMENTS: &str = include_str!("fixtures/ghes/review_comments.json");
+const WORKFLOW_RUNS: &str = include_str!("fixtures/ghes/workflow_runs.json");
+const CHECK_RUNS: &str = include_str!("fixtures/ghes/check_runs.json");
+const STATUS: &str = include_str!("fixtures/ghes/status.json");
+const GRAPHQL_PULL_REQUESTS: &str = include_str!("fixtures/ghes/graphql_pull_requests.json");
+const LOGS_ZIP: &[u8] = include_bytes!("fixtures/ghes/logs.zip");
+
+const HEAD_SHA: &str = "5d2f0c9e1a7b3c4d5e6f708192a3b4c5d6e7f809";
+const DIFF: &str = "diff --git a/src/resize.rs b/src/resize.rs\n--- a/src/resize.rs\n+++ b/src/resize.rs\n@@ -1 +1 @@\n-let scale = 1;\n+let scale = ratio;\n";
+
+/// Answer the way a GitHub Enterprise Server does: REST under `/api/v3`,
+/// GraphQL at `/api/graphql`, and log archives redirected to storage
+/// outside the API. Anything else is a 404, as on a real server.
+fn ghes(request: &Request) -> Response {
+    let path = request.path.split('?').next().unwrap();
+    if path == "/api/graphql" && request.method == "POST" {
+        return Response::ok(GRAPHQL_PULL_REQUESTS);
+    }
+    if path == "/_storage/logs/4401.zip" {
+        return Response::ok(LOGS_ZIP);
+    }
+    let Some(route) = path.strip_prefix("/api/v3/repos/acme/widgets/") else {
+        return Response::status(404).body(r#"{"message": "Not Found"}"#);
+    };
+    let checks = format!("commits/{}/check-runs", HEAD_SHA);
+    let status = format!("commits/{}/status", HEAD_SHA);
+    match route {
+        "pulls" => Response::ok(format!("[{}]", PULL_REQUEST)),
+        "pulls/7" if request.header("accept") == Some("application/vnd.github.v3.diff") => Response::ok(DIFF),
+        "pulls/7" => Response::ok(PULL_REQUEST),
+        "pulls/7/commits" => Response::ok(COMMITS),
+        "issues/7/comments" => Response::ok(ISSUE_COMMENTS),
+        "pulls/7/reviews" => Response::ok(REVIEWS),
+        "pulls/7/comments" => Response::ok(REVIEW_COMMENTS),
+        "actions/runs" => Response::ok(WORKFLOW_RUNS),
+        "actions/runs/4401/attempts/1/logs" | "actions/runs/4401/logs" => {
+            Response::status(302).header("Location", "/_storage/logs/4401.zip")
+        }
+        _ if route == checks => Response::ok(CHECK_RUNS),
+        _ if route == status => Response::ok(STATUS),
+        _ => Response::status(404).body(r#"{"message": "Not Found"}"#),
+    }
+}
+
+/// A client configured with the server's bare host, as a user would give it.
+fn client(server: &MockServer) -> GitHubClient {
+    let api = ApiClient::new("test-token".to_string(), DEFAULT_API_URL).unwrap();
+    GitHubClient::from_api(api, "acme".to_string(), "widgets".to_string()).with_api_url(&server.url)
+}
+
+fn assert_all_enterprise_paths(server: &MockServer) {
+    for request in server.requests() {
+        assert!(
+            request.path.starts_with("/api/v3/")
+                || request.path == "/api/graphql"
+                || request.path.starts_with("/_storage/"),
+            "request outside the GHES layout: {} {}",
+            request.method,
+            request.path
+        );
+    }
+}
+
+#[tokio::test]
+async fn rest_sync_uses_enterprise_api_root() {
+    let server = MockServer::start(ghes).await;
+    let client = client(&server);
+
+    let prs = client.list_recent_prs(10, false).await.unwrap();
+    assert_eq!(prs.len(), 1);
+    let details = client.get_pull_request_details(prs[0].number).await.unwrap();
+
+    assert_eq!(details.title, "Fix widget resize on HiDPI displays");
+    assert_eq!(details.base_branch, "main");
+    assert!(details.diff.contains("+let scale = ratio;"));
+    assert_eq!(details.commits.len(), 2);
+
+    let sources: Vec<_> = details.checks.iter().map(|c| (c.source, c.name.as_str())).collect();
+    assert_eq!(
+        sources,
+        [
+            (CheckSource::Actions, "CI"),
+            (CheckSource::CheckRun, "buildkite/widgets"),
+            (CheckSource::Status, "ci/jenkins"),
+        ]
+    );
+    assert_eq!(
+        details.checks[0].logs_url.as_deref(),
+        Some(format!("{}/api/v3/repos/acme/widgets/actions/runs/4401/attempts/1/logs", server.url).as_str())
+    );
+    assert_all_enterprise_paths(&server);
+}
+
+#[tokio::test]
+async fn graphql_sync_uses_enterprise_graphql_endpoint() {
+    let server = MockServer::start(ghes).await;
+    let client = client(&server);
+
+    let details = client.get_pull_request_details_batch(&[7]).await.unwrap();
+    let details = details.into_iter().next().unwrap().unwrap();
+
+    assert_eq!(details.labels, ["bug"]);
+    assert!(details.diff.contains("+let scale = ratio;"));
+    let actions = &details.checks[0];
+    assert_eq!((actions.source, actions.id.as_str(), actions.name.as_str()), (CheckSource::Actions, "4401", "CI"));
+    assert_eq!(
+        actions.logs_url.as_deref(),
+        Some(format!("{}/api/v3/repos/acme/widgets/actions/runs/4401/logs", server.url).as_str())
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/api/graphql");
+    assert_all_enterprise_paths(&server);
+}
+
+#[tokio::test]
+async fn graphql_sync_falls_back_to_rest_for_truncated_prs() {
+    // The query got the first of the PR's commits and was told there are more
+    let mut response: serde_json::Value = serde_json::from_str(GRAPHQL_PULL_REQUESTS).unwrap();
+    let commits = &mut response["data"]["repository"]["pr0"]["commits"];
+    commits["nodes"].as_array_mut().unwrap().truncate(1);
+    commits["pageInfo"] = serde_json::json!({ "hasNextPage": true });
+    let truncated = response.to_string();
+    let server = MockServer::start(move |request| match request.path.as_str() {
+        "/api/graphql" => Response::ok(truncated.clone()),
+        _ => ghes(request),
+    })
+    .await;
+
+    let details = client(&server).get_pull_request_details_batch(&[7]).await.unwrap();
+    let details = details.into_iter().next().unwrap().unwrap();
+
+    assert_eq!(details.commits.len(), 2);
+    assert_eq!(details.checks.len(), 3);
+    let paths: Vec<_> = server.requests().into_iter().map(|r| r.path).collect();
+    assert!(paths.iter().any(|p| p.starts_with("/api/v3/repos/acme/widgets/pulls/7/commits")), "{:?}", paths);
+}
+
+#[tokio::test]
+async fn rest_and_graphql_sync_index_the_same_pr() {
+    let server = MockServer::start(ghes).await;
+    let client = client(&server);
+
+    let rest = client.get_pull_request_details(7).await.unwrap();
+    let graphql = client.get_pull_request_details_batch(&[7]).await.unwrap();
+    let graphql = graphql.into_iter().next().unwrap().unwrap();
+
+    // Everything but the checks, which come from different APIs and
+    // fixtures, should be indexed the same whichever path fetched the PR
+    let indexed = |pr: &PullRequestDetails| {
+        format!(
+            "{:?}",
+            (
+                (&pr.title, &pr.description, &pr.author, &pr.labels, &pr.base_branch, pr.merged_at),
+                (&pr.state, pr.mergeable, pr.merged, &pr.diff),
+                (&pr.comments, &pr.review_comments, &pr.commits),
+            )
+        )
+    };
+    assert_eq!(indexed(&rest), indexed(&graphql));
+    assert_eq!(
+        rest.comments,
+        [
+            "Reproduced on a 4K monitor before this change.",
+            "Looks right; the ratio should also apply to the minimum size.",
+        ]
+    );
+    assert_eq!(rest.review_comments.len(), 1);
+}
+
+#[tokio::test]
+async fn ci_failures_download_enterprise_logs() {
+    let server = MockServer::start(ghes).await;
+
+    let report = find_ci_failures(&client(&server), 7, 10).await.unwrap();
+
+    assert_eq!(report.head_sha, HEAD_SHA);
+    assert_eq!(report.failures.len(), 1);
+    let failure = &report.failures[0];
+    assert_eq!(failure.workflow, "CI");
+    assert_eq!(failure.job.as_deref(), Some("build"));
+    assert_eq!(failure.step.as_deref(), Some("Run tests"));
+    assert_eq!(failure.location.as_deref(), Some("src/resize.rs:31"));
+    assert_eq!(
+        failure.excerpt.last().map(String::as_str),
+        Some("##[error]Process completed with exit code 101.")
+    );
+    assert!(server.requests().iter().any(|r| r.path == "/_storage/logs/4401.zip"));
+    assert_all_enterprise_paths(&server);
+}
+
+#[tokio::test]
+async fn resolved_failures_come_from_the_last_failing_commit() {
+    let server = MockServer::start(ghes).await;
+    let client = client(&server);
+    let mut pr = client.get_pull_request_details(7).await.unwrap();
+
+    // The head's checks failed, so there is nothing resolved yet
+    assert!(resolved_failures(&client, &pr).await.is_empty());
+
+    // A later commit fixed them and passed
+    pr.checks.clear();
+    pr.commits.push(CommitInfo {
+        sha: "9999999fffffff".to_string(),
+        message: "Really fix resize test".to_string(),
+        author: "Dana".to_string(),
+        author_email: None,
+        url: String::new(),
+    });
+    let failures = resolved_failures(&client, &pr).await;
+
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].commit_sha, HEAD_SHA);
+    assert_eq!(failures[0].location.as_deref(), Some("src/resize.rs:31"));
+    assert!(!failures[0].excerpt.is_empty());
+}
+
+#[test]
+fn api_base_url_maps_hosts_to_api_roots() {
+    assert_eq!(api_base_url("https://ghe.example.com"), "https://ghe.example.com/api/v3");
+    assert_eq!(api_base_url("https://ghe.example.com/api/v3/"), "https://ghe.example.com/api/v3");
+    assert_eq!(api_base_url("https://github.com"), DEFAULT_API_URL);
+    assert_eq!(api_base_url("https://api.github.com/"), DEFAULT_API_URL);
+}
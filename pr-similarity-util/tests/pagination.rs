@@ -0,0 +1,137 @@
+mod support;
+
+use pr_similarity_search::api::{next_link, ApiClient};
+use serde::Deserialize;
+use support::{temp_dir, MockServer, Request, Response};
+
+const COMMIT_PAGES: [&str; 3] = [
+    include_str!("fixtures/pagination/commits_page1.json"),
+    include_str!("fixtures/pagination/commits_page2.json"),
+    include_str!("fixtures/pagination/commits_page3.json"),
+];
+
+const WORKFLOW_RUN_PAGES: [&str; 2] = [
+    include_str!("fixtures/pagination/workflow_runs_page1.json"),
+    include_str!("fixtures/pagination/workflow_runs_page2.json"),
+];
+
+#[derive(Deserialize)]
+struct Commit {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct Run {
+    id: u64,
+    conclusion: String,
+}
+
+fn page_number(request: &Request) -> usize {
+    request
+        .path
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix("page="))
+        .map_or(1, |page| page.parse().unwrap())
+}
+
+/// Serve `pages` the way GitHub does, with `Link` headers naming the next
+/// and last pages. `links_past_end` keeps advertising a next page after the
+/// last one, as can happen when items are added mid-pagination.
+async fn paged_server(pages: &'static [&'static str], links_past_end: bool) -> MockServer {
+    let base = std::sync::Arc::new(std::sync::OnceLock::<String>::new());
+    let server_base = base.clone();
+    let server = MockServer::start(move |request| {
+        let page = page_number(request);
+        let Some(body) = pages.get(page - 1) else {
+            return Response::status(404).body(r#"{"message": "Not Found"}"#);
+        };
+        let route = request.path.split('?').next().unwrap();
+        let url = |page: usize| format!("{}{}?per_page=100&page={}", server_base.get().unwrap(), route, page);
+
+        let mut links = Vec::new();
+        if page < pages.len() || links_past_end {
+            links.push(format!("<{}>; rel=\"next\"", url(page + 1)));
+        }
+        links.push(format!("<{}>; rel=\"last\"", url(pages.len())));
+        Response::ok(*body)
+            .header("Link", links.join(", "))
+            .header("ETag", format!("\"page-{}\"", page))
+    })
+    .await;
+    base.set(server.url.clone()).unwrap();
+    server
+}
+
+fn client(server: &MockServer) -> ApiClient {
+    ApiClient::new("test-token".to_string(), &server.url).unwrap()
+}
+
+#[tokio::test]
+async fn get_all_follows_link_headers() {
+    let server = paged_server(&COMMIT_PAGES, false).await;
+
+    let commits: Vec<Commit> = client(&server).get_all("/repos/block/goose/pulls/812/commits", &[]).await.unwrap();
+
+    let shas: Vec<_> = commits.iter().map(|c| c.sha.as_str()).collect();
+    assert_eq!(shas, ["3f2a91c0d4", "8b71e2f5aa", "c09d44e1b7", "1e5f8a3d92", "7aa0b6c3e8"]);
+
+    let paths: Vec<_> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(
+        paths,
+        [
+            "/repos/block/goose/pulls/812/commits?per_page=100",
+            "/repos/block/goose/pulls/812/commits?per_page=100&page=2",
+            "/repos/block/goose/pulls/812/commits?per_page=100&page=3",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn get_all_in_stops_at_total_count() {
+    let server = paged_server(&WORKFLOW_RUN_PAGES, true).await;
+
+    let runs: Vec<Run> = client(&server)
+        .get_all_in(
+            "/repos/block/goose/actions/runs",
+            &[("head_sha", "7aa0b6c3e8".to_string())],
+            "workflow_runs",
+        )
+        .await
+        .unwrap();
+
+    let ids: Vec<_> = runs.iter().map(|r| r.id).collect();
+    assert_eq!(ids, [12876001, 12876002, 12876003]);
+    assert_eq!(runs[2].conclusion, "failure");
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(
+        server.requests()[0].path,
+        "/repos/block/goose/actions/runs?head_sha=7aa0b6c3e8&per_page=100"
+    );
+}
+
+#[tokio::test]
+async fn cached_pages_keep_their_next_link() {
+    let server = paged_server(&COMMIT_PAGES, false).await;
+    let cache = temp_dir("cached-pages");
+
+    for _ in 0..2 {
+        let client = client(&server).with_response_cache(cache.clone());
+        let commits: Vec<Commit> = client.get_all("/repos/block/goose/pulls/812/commits", &[]).await.unwrap();
+        assert_eq!(commits.len(), 5);
+    }
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 6);
+    assert!(requests[3..].iter().all(|r| r.header("if-none-match").is_some()));
+    std::fs::remove_dir_all(cache).unwrap();
+}
+
+#[test]
+fn next_link_reads_rel_next() {
+    let header = r#"<https://api.github.com/repositories/1/pulls?page=1>; rel="prev", <https://api.github.com/repositories/1/pulls?page=3>; rel="next", <https://api.github.com/repositories/1/pulls?page=9>; rel="last""#;
+    assert_eq!(
+        next_link(header).as_deref(),
+        Some("https://api.github.com/repositories/1/pulls?page=3")
+    );
+    assert_eq!(next_link(r#"<https://api.github.com/x?page=1>; rel="first""#), None);
+}
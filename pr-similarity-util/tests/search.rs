@@ -0,0 +1,578 @@
+mod support;
+
+use pr_similarity_search::ci_failure::{failure_signature, CiFailure};
+use pr_similarity_search::config::SearchConfig;
+use chrono::{TimeZone, Utc};
+use pr_similarity_search::filter::{parse_date, parse_end_date, SearchFilters};
+use pr_similarity_search::github::{Check, CheckSource, CommitInfo, PullRequestDetails, ReviewComment};
+use pr_similarity_search::output::{render_pr, OutputFormat};
+use pr_similarity_search::sync_state::SyncOptions;
+use pr_similarity_search::{PRSearchIndex, RepoFilter, SearchResult};
+use support::temp_dir;
+
+/// A merged PR with nothing but a title and a diff.
+fn pull_request(number: u64, title: &str, diff: &str) -> PullRequestDetails {
+    PullRequestDetails {
+        repo: "block/goose".to_string(),
+        number,
+        title: title.to_string(),
+        url: format!("https://github.com/block/goose/pull/{}", number),
+        description: String::new(),
+        author: "alice".to_string(),
+        labels: Vec::new(),
+        base_branch: "main".to_string(),
+        merged_at: None,
+        comments: Vec::new(),
+        review_comments: Vec::new(),
+        state: "closed".to_string(),
+        mergeable: None,
+        merged: true,
+        checks: Vec::new(),
+        ci_failures: Vec::new(),
+        commits: Vec::new(),
+        diff: diff.to_string(),
+    }
+}
+
+fn index(name: &str, prs: &[PullRequestDetails]) -> PRSearchIndex {
+    let mut index = PRSearchIndex::with_config(Vec::new(), &temp_dir(name), SearchConfig::default()).unwrap();
+    index.import_prs(prs).unwrap();
+    index
+}
+
+/// Without recency or reranking, a result's score is exactly what its
+/// fields contributed to the query.
+fn assert_scored_by_query_alone(results: &[SearchResult]) {
+    assert!(!results.is_empty());
+    for result in results {
+        let fields: f32 = result.field_scores.iter().map(|field| field.score).sum();
+        assert!(
+            (result.score - fields).abs() < 1e-3,
+            "#{} scored {} but its fields sum to {}",
+            result.pr_number,
+            result.score,
+            fields
+        );
+    }
+}
+
+fn retry_prs() -> Vec<PullRequestDetails> {
+    let diff = |path: &str| {
+        format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -1 +1 @@\n-retry(1)\n+retry(3)\n",
+            path = path
+        )
+    };
+    let mut other = pull_request(2, "Retry provider requests on timeout", &diff("server/retry.go"));
+    other.repo = "block/other".to_string();
+    other.url = "https://github.com/block/other/pull/2".to_string();
+    let mut goose = pull_request(1, "Retry provider requests", &diff("crates/goose/src/providers/retry.rs"));
+    goose.labels = vec!["bug".to_string()];
+    goose.merged_at = Some(Utc.with_ymd_and_hms(2024, 5, 1, 15, 30, 0).unwrap());
+    goose.checks = vec![Check {
+        source: CheckSource::Actions,
+        id: "1".to_string(),
+        name: "CI".to_string(),
+        status: "completed".to_string(),
+        conclusion: Some("success".to_string()),
+        details_url: String::new(),
+        logs_url: None,
+        head_sha: String::new(),
+        started_at: None,
+        completed_at: None,
+    }];
+    let mut bump = pull_request(3, "Bump the retry count", &diff("ui/desktop/retry.ts"));
+    bump.merged_at = Some(Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap());
+    vec![goose, other, bump]
+}
+
+#[test]
+fn filters_do_not_change_scores() {
+    let index = index("filter-scores", &retry_prs());
+    let unfiltered = index.search_in("retry provider", 10, &SearchFilters::default()).unwrap();
+    assert_eq!(unfiltered.len(), 3);
+    assert_scored_by_query_alone(&unfiltered);
+
+    let filters = SearchFilters {
+        repos: RepoFilter::Only(vec!["block/goose".to_string()]),
+        path_prefix: Some("crates/goose".to_string()),
+        ..SearchFilters::default()
+    };
+    let filtered = index.search_in("retry provider", 10, &filters).unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert_scored_by_query_alone(&filtered);
+    let same = unfiltered.iter().find(|r| r.pr_number == 1).unwrap();
+    assert_eq!(filtered[0].score, same.score);
+}
+
+#[test]
+fn metadata_filters_do_not_change_scores() {
+    let index = index("metadata-filter-scores", &retry_prs());
+    let unfiltered = index.search_in("retry provider", 10, &SearchFilters::default()).unwrap();
+
+    let filters = SearchFilters {
+        author: Some("alice".to_string()),
+        labels: vec!["bug".to_string()],
+        base_branch: Some("main".to_string()),
+        checks_status: Some("all_passed".to_string()),
+        merged_after: Some(parse_date("2024-04-01").unwrap()),
+        states: vec!["merged".to_string()],
+        ..SearchFilters::default()
+    };
+    let filtered = index.search_in("retry provider", 10, &filters).unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert_scored_by_query_alone(&filtered);
+    let same = unfiltered.iter().find(|r| r.pr_number == 1).unwrap();
+    assert_eq!(filtered[0].score, same.score);
+}
+
+#[test]
+fn merged_before_a_date_includes_that_day() {
+    let index = index("merged-before-day", &retry_prs());
+    let filters = SearchFilters {
+        merged_before: Some(parse_end_date("2024-05-01").unwrap()),
+        ..SearchFilters::default()
+    };
+
+    let results = index.search_in("retry", 10, &filters).unwrap();
+    let numbers: Vec<_> = results.iter().map(|r| r.pr_number).collect();
+    assert_eq!(numbers, [1]);
+
+    let timestamp = parse_end_date("2024-05-01T12:00:00Z").unwrap();
+    assert_eq!(timestamp, Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap());
+}
+
+#[test]
+fn prs_without_checks_are_not_reported_as_passing() {
+    let index = index("no-checks", &retry_prs());
+    let checks = |status: &str| {
+        let filters = SearchFilters {
+            checks_status: Some(status.to_string()),
+            ..SearchFilters::default()
+        };
+        let mut numbers: Vec<_> = index
+            .search_in("retry", 10, &filters)
+            .unwrap()
+            .iter()
+            .map(|r| (r.pr_number, r.checks_status.clone()))
+            .collect();
+        numbers.sort();
+        numbers
+    };
+
+    assert_eq!(checks("all_passed"), [(1, "all_passed".to_string())]);
+    assert_eq!(checks("none"), [(2, "none".to_string()), (3, "none".to_string())]);
+}
+
+#[test]
+fn recency_keeps_the_raw_score_of_old_prs() {
+    let mut config = SearchConfig::default();
+    config.set_recency_half_life(0.5).unwrap();
+    let mut decayed = PRSearchIndex::with_config(Vec::new(), &temp_dir("recency-old"), config).unwrap();
+    decayed.import_prs(&retry_prs()).unwrap();
+    let plain = index("recency-plain", &retry_prs());
+
+    let results = decayed.search_in("retry provider", 10, &SearchFilters::default()).unwrap();
+    let old = results.iter().find(|r| r.pr_number == 1).unwrap();
+    let undecayed = plain.search_in("retry provider", 10, &SearchFilters::default()).unwrap();
+    let undecayed = undecayed.iter().find(|r| r.pr_number == 1).unwrap();
+
+    assert!(old.score > 0.0 && old.recency_factor.unwrap() > 0.0);
+    assert!((old.bm25_score - undecayed.score).abs() < 1e-4, "{} vs {}", old.bm25_score, undecayed.score);
+    serde_json::to_string(&results).unwrap();
+}
+
+#[test]
+fn enabling_reranking_embeds_prs_indexed_before() {
+    let cache = temp_dir("backfill-vectors");
+    let model = cache.join("model.vec");
+    std::fs::write(&model, "retry 1 0\nprovider 0 1\nbump 1 1\n").unwrap();
+    PRSearchIndex::with_config(Vec::new(), &cache, SearchConfig::default())
+        .unwrap()
+        .import_prs(&retry_prs())
+        .unwrap();
+
+    let config = SearchConfig {
+        embedding_model: Some(model),
+        ..SearchConfig::default()
+    };
+    let index = PRSearchIndex::with_config(Vec::new(), &cache, config).unwrap();
+    let results = index.search_in("retry provider", 10, &SearchFilters::default()).unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r.semantic_score.is_some()), "{:?}", results);
+}
+
+#[test]
+fn snippets_of_compound_identifiers_mark_whole_matches() {
+    let diff = "diff --git a/ui/form.tsx b/ui/form.tsx\n\
+                --- a/ui/form.tsx\n\
+                +++ b/ui/form.tsx\n\
+                @@ -1,2 +1,2 @@\n\
+                -export function GooseResponseForm(props) {\n\
+                +export function GooseResponseForm({ response }) {\n";
+    let index = index("compound-snippets", &[pull_request(1, "Pass the response to the form", diff)]);
+
+    let results = index
+        .search_in("gooseresponseform response", 10, &SearchFilters::default())
+        .unwrap();
+
+    let snippet = results[0].snippets.iter().find(|s| s.field == "diff").unwrap();
+    for pair in snippet.highlighted.windows(2) {
+        assert!(pair[0].1 < pair[1].0, "overlapping highlights {:?}", snippet.highlighted);
+    }
+    let marked = snippet.marked("**");
+    assert!(marked.contains("**GooseResponseForm**"), "{}", marked);
+    assert!(marked.contains("{ **response**"), "{}", marked);
+}
+
+#[test]
+fn show_refuses_to_guess_between_repositories() {
+    let mut same_number = pull_request(1, "Another first PR", "");
+    same_number.repo = "block/other".to_string();
+    let mut prs = retry_prs();
+    prs.push(same_number);
+    let index = index("ambiguous-show", &prs);
+
+    let error = index.get_pr_in(1, &RepoFilter::All).unwrap_err();
+    assert!(error.to_string().contains("block/goose, block/other"), "{}", error);
+
+    let only_goose = RepoFilter::Only(vec!["block/goose".to_string()]);
+    let pr = index.get_pr_in(1, &only_goose).unwrap().unwrap();
+    assert_eq!(pr.title, "Retry provider requests");
+}
+
+#[test]
+fn markdown_closes_the_fence_after_a_truncated_diff() {
+    let line = "+// a long line of generated code that goes on and on\n";
+    let diff = format!(
+        "diff --git a/gen.rs b/gen.rs\n--- a/gen.rs\n+++ b/gen.rs\n@@ -0,0 +1,2000 @@\n{}",
+        line.repeat(2000)
+    );
+    let index = index("truncated-show", &[pull_request(9, "Regenerate bindings", &diff)]);
+    let pr = index.get_pr_in(9, &RepoFilter::All).unwrap().unwrap();
+    assert!(pr.diff_truncated);
+
+    let markdown = render_pr(OutputFormat::Markdown, &pr).unwrap();
+    assert_eq!(markdown.matches("```").count(), 2);
+    assert!(markdown.ends_with("\n... diff truncated\n```\n"), "{}", &markdown[markdown.len() - 80..]);
+}
+
+fn commit(sha: &str, message: &str) -> CommitInfo {
+    CommitInfo {
+        sha: sha.to_string(),
+        message: message.to_string(),
+        author: "alice".to_string(),
+        author_email: None,
+        url: String::new(),
+    }
+}
+
+/// PR `number` fixed a CI failure on its first commit with a second one.
+fn fixed_failure(number: u64, title: &str, excerpt: &[&str]) -> PullRequestDetails {
+    let mut pr = pull_request(number, title, "");
+    let failed = format!("{:040x}", number);
+    pr.commits = vec![commit(&failed, "First attempt"), commit("f1xed", &format!("Fix {}\n\nDetails", title))];
+    pr.ci_failures = vec![CiFailure {
+        workflow: "CI".to_string(),
+        source: CheckSource::Actions,
+        commit_sha: failed,
+        url: format!("https://github.com/block/goose/actions/runs/{}", number),
+        job: Some("test".to_string()),
+        step: Some("Run cargo test".to_string()),
+        location: None,
+        excerpt: excerpt.iter().map(|line| line.to_string()).collect(),
+    }];
+    pr
+}
+
+#[test]
+fn similar_failures_find_the_pr_that_fixed_them() {
+    let timeout = fixed_failure(
+        10,
+        "Raise the provider timeout",
+        &[
+            "thread 'providers::retry' panicked at /home/runner/work/goose/goose/crates/goose/src/providers/retry.rs:88:9:",
+            "request timed out after 30.0s (attempt 3 of 3, id 5d2f0c9e1a7b)",
+        ],
+    );
+    let lint = fixed_failure(11, "Fix lint", &["npm ERR! eslint found 2 problems in ui/desktop/src/App.tsx"]);
+    let index = index("similar-failures", &[timeout, lint]);
+
+    // The same failure on another machine, commit and run
+    let log = "2024-06-02T08:00:00.0000000Z thread 'providers::retry' panicked at D:\\a\\goose\\crates\\goose\\src\\providers\\retry.rs:90:13:\n\
+               2024-06-02T08:00:00.0000000Z request timed out after 31.5s (attempt 3 of 3, id 0a9e8d7c6b5f)\n";
+    let matches = index.similar_failures(log, 10, &SearchFilters::default()).unwrap();
+
+    let best = &matches[0];
+    assert_eq!((best.pr_number, best.title.as_str()), (10, "Raise the provider timeout"));
+    assert_eq!(best.signature, failure_signature(log));
+    assert_eq!(best.check, "CI / test / Run cargo test");
+    assert_eq!(best.failed_commit, format!("{:040x}", 10));
+    assert_eq!(best.fixing_commits, ["f1xed Fix Raise the provider timeout"]);
+    assert!(matches.iter().skip(1).all(|m| m.score < best.score), "{:?}", matches);
+}
+
+#[test]
+fn unmerged_prs_are_ranked_apart_from_merged_ones() {
+    let mut prs = retry_prs();
+    let mut abandoned = pull_request(4, "Retry provider requests forever", "");
+    abandoned.merged = false;
+    let mut open = pull_request(5, "Retry provider requests with jitter", "");
+    open.merged = false;
+    open.state = "open".to_string();
+    prs.extend([abandoned, open]);
+
+    // Unmerged PRs are only indexed when asked for
+    assert_eq!(index("merged-only-import", &prs).search("retry provider", 10).unwrap().len(), 3);
+
+    let mut index = PRSearchIndex::with_config(Vec::new(), &temp_dir("unmerged-import"), SearchConfig::default()).unwrap();
+    index.set_sync_options(SyncOptions {
+        include_unmerged: true,
+        ..SyncOptions::default()
+    });
+    assert_eq!(index.import_prs(&prs).unwrap(), 5);
+
+    let (merged, unmerged) = SearchFilters::default().merged_and_unmerged();
+    let numbers = |filters: &SearchFilters| {
+        let mut results: Vec<_> = index
+            .search_in("retry provider", 10, filters)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.pr_number, r.status))
+            .collect();
+        results.sort();
+        results
+    };
+    let status = |number: u64, status: &str| (number, status.to_string());
+    assert_eq!(numbers(&merged), [status(1, "merged"), status(2, "merged"), status(3, "merged")]);
+    assert_eq!(numbers(&unmerged.unwrap()), [status(4, "closed"), status(5, "open")]);
+
+    // Asking for states gives a single ranking of just those
+    let closed = SearchFilters {
+        states: vec!["closed".to_string()],
+        ..SearchFilters::default()
+    };
+    let (closed, none) = closed.merged_and_unmerged();
+    assert!(none.is_none());
+    assert_eq!(numbers(&closed), [status(4, "closed")]);
+}
+
+#[test]
+fn review_comments_are_found_by_path_and_quoted_code() {
+    let comment = |path: &str, diff_hunk: &str, body: &str, line: u64| ReviewComment {
+        author: "bob".to_string(),
+        body: body.to_string(),
+        path: path.to_string(),
+        diff_hunk: diff_hunk.to_string(),
+        line: Some(line),
+        url: format!("https://github.com/block/goose/pull/1#discussion_r{}", line),
+    };
+    let mut pr = pull_request(1, "Retry provider requests", "");
+    pr.review_comments = vec![
+        comment(
+            "crates/goose/src/providers/retry.rs",
+            "@@ -10,3 +10,4 @@\n fn retry() {\n+    let max_attempts = 3;",
+            "Should this come from the config?",
+            12,
+        ),
+        comment("ui/desktop/src/App.tsx", "@@ -1 +1 @@\n-useState(0)\n+useState(1)", "Nit: name the magic number", 1),
+    ];
+    let index = index("review-comment-search", &[pr]);
+    let search = |query: &str| {
+        index
+            .search_review_comments(query, 10, &SearchFilters::default())
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.path, result.line))
+            .collect::<Vec<_>>()
+    };
+    let retry = || vec![("crates/goose/src/providers/retry.rs".to_string(), Some(12))];
+
+    assert_eq!(search("providers/retry.rs"), retry());
+    assert_eq!(search("max_attempts"), retry());
+    assert_eq!(search("config"), retry());
+    assert_eq!(search("usestate"), [("ui/desktop/src/App.tsx".to_string(), Some(1))]);
+
+    let results = index.search_review_comments("magic", 10, &SearchFilters::default()).unwrap();
+    assert_eq!(results[0].body, "Nit: name the magic number");
+    assert_eq!(results[0].url, "https://github.com/block/goose/pull/1#discussion_r1");
+}
+
+/// A diff with one hunk per `(path, old_start, added line)`.
+fn diff_of(hunks: &[(&str, u64, &str)]) -> String {
+    let mut diff = String::new();
+    let mut last_path = "";
+    for &(path, start, line) in hunks {
+        if path != last_path {
+            diff.push_str(&format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n", path = path));
+            last_path = path;
+        }
+        diff.push_str(&format!("@@ -{start},2 +{start},3 @@ fn context()\n let a = 1;\n+{line}\n let b = 2;\n"));
+    }
+    diff
+}
+
+#[test]
+fn hunk_search_points_at_the_matching_region() {
+    let diff = diff_of(&[
+        ("crates/goose/src/providers/retry.rs", 10, "let backoff = jitter(attempt);"),
+        ("crates/goose/src/providers/retry.rs", 40, "log::warn!(\"giving up\");"),
+        ("ui/desktop/src/retry.ts", 5, "const backoff = 2 ** attempt;"),
+    ]);
+    let index = index("hunk-search", &[pull_request(1, "Retry with jitter", &diff)]);
+    let search = |query: &str, filters: &SearchFilters| {
+        index
+            .search_hunks(query, 10, filters)
+            .unwrap()
+            .into_iter()
+            .map(|hunk| (hunk.path.clone(), hunk.new_start, hunk.new_end()))
+            .collect::<Vec<_>>()
+    };
+    let region = |path: &str, start: u64| (path.to_string(), start, start + 2);
+
+    assert_eq!(
+        search("jitter", &SearchFilters::default()),
+        [region("crates/goose/src/providers/retry.rs", 10)]
+    );
+    assert_eq!(
+        search("giving", &SearchFilters::default()),
+        [region("crates/goose/src/providers/retry.rs", 40)]
+    );
+    let mut backoff = search("backoff", &SearchFilters::default());
+    backoff.sort();
+    assert_eq!(
+        backoff,
+        [region("crates/goose/src/providers/retry.rs", 10), region("ui/desktop/src/retry.ts", 5)]
+    );
+    let desktop = SearchFilters {
+        path_prefix: Some("ui/desktop".to_string()),
+        ..SearchFilters::default()
+    };
+    assert_eq!(search("backoff", &desktop), [region("ui/desktop/src/retry.ts", 5)]);
+    assert!(search("retry.ts", &SearchFilters::default()).contains(&region("ui/desktop/src/retry.ts", 5)));
+
+    let hunks = index.search_hunks("jitter", 10, &SearchFilters::default()).unwrap();
+    assert_eq!((hunks[0].repo.as_str(), hunks[0].pr_number), ("block/goose", 1));
+    assert_eq!((hunks[0].old_start, hunks[0].old_lines), (10, 2));
+    assert_eq!(hunks[0].header, "@@ -10,2 +10,3 @@ fn context()");
+}
+
+#[test]
+fn file_search_ranks_prs_by_shared_files() {
+    let prs = [
+        pull_request(
+            1,
+            "Retry provider requests",
+            &diff_of(&[
+                ("crates/goose/src/providers/retry.rs", 1, "a"),
+                ("crates/goose/src/providers/mod.rs", 1, "b"),
+            ]),
+        ),
+        pull_request(2, "Tidy the providers", &diff_of(&[("crates/goose/src/providers/openai.rs", 1, "c")])),
+        pull_request(3, "Desktop styling", &diff_of(&[("ui/desktop/src/App.css", 1, "d")])),
+    ];
+    let index = index("file-search", &prs);
+    let paths = ["./crates/goose/src/providers/retry.rs".to_string(), "crates/goose/src/providers/mod.rs".to_string()];
+
+    let results = index.search_by_files(&paths, 10, &SearchFilters::default()).unwrap();
+
+    let ranked: Vec<_> = results.iter().map(|result| result.pr_number).collect();
+    assert_eq!(ranked, [1, 2]);
+    assert_eq!(results[0].shared_files, ["crates/goose/src/providers/retry.rs", "crates/goose/src/providers/mod.rs"]);
+    assert!(results[1].shared_files.is_empty());
+    assert!(results[0].score > results[1].score && results[1].score > 0.0, "{:?}", results);
+    assert_eq!(results[0].bm25_score, 0.0);
+
+    let others = SearchFilters {
+        author: Some("bob".to_string()),
+        ..SearchFilters::default()
+    };
+    assert!(index.search_by_files(&paths, 10, &others).unwrap().is_empty());
+    assert_eq!(index.search_by_files(&paths, 1, &SearchFilters::default()).unwrap().len(), 1);
+}
+
+#[test]
+fn child_documents_do_not_change_pr_scores() {
+    let path = "crates/goose/src/providers/retry.rs";
+    let plain = pull_request(1, "Retry provider requests", &diff_of(&[(path, 1, "retry(3)")]));
+    let mut busy = pull_request(
+        1,
+        "Retry provider requests",
+        &diff_of(&[(path, 1, "retry(3)"), (path, 20, "sleep(1)"), (path, 40, "sleep(2)")]),
+    );
+    busy.ci_failures = fixed_failure(1, "Retry provider requests", &["error: provider retry timed out"]).ci_failures;
+    busy.review_comments = vec![ReviewComment {
+        author: "bob".to_string(),
+        body: "Fine".to_string(),
+        path: path.to_string(),
+        diff_hunk: "@@ -1 +1 @@\n+retry(3)".to_string(),
+        line: Some(1),
+        url: "https://github.com/block/goose/pull/1#discussion_r1".to_string(),
+    }];
+    let field_score = |index: &PRSearchIndex, query: &str, field: &str| {
+        let results = index.search(query, 10).unwrap();
+        results[0].field_scores.iter().find(|score| score.field == field).unwrap().score
+    };
+
+    let plain = index("plain-children", &[plain, pull_request(2, "Provider docs", &diff_of(&[("README.md", 1, "x")]))]);
+    let busy = index("busy-children", &[busy, pull_request(2, "Provider docs", &diff_of(&[("README.md", 1, "x")]))]);
+
+    // The PR documents' titles and files are the same in both indexes;
+    // only the number of hunk, review comment and CI failure documents differs
+    assert_eq!(field_score(&plain, "retry", "title"), field_score(&busy, "retry", "title"));
+    assert_eq!(field_score(&plain, "providers", "files"), field_score(&busy, "providers", "files"));
+}
+
+#[test]
+fn recency_ranks_equal_matches_by_merge_date() {
+    let now = Utc::now();
+    let dated = |number: u64, days_ago: Option<i64>| {
+        let mut pr = pull_request(number, "Retry provider requests", "");
+        pr.merged_at = days_ago.map(|days| now - chrono::TimeDelta::days(days));
+        pr
+    };
+    let prs = [dated(1, Some(300)), dated(2, None), dated(3, Some(2))];
+    let mut config = SearchConfig::default();
+    config.set_recency_half_life(30.0).unwrap();
+    let mut index = PRSearchIndex::with_config(Vec::new(), &temp_dir("recency-order"), config).unwrap();
+    index.import_prs(&prs).unwrap();
+
+    let results = index.search("retry provider", 10).unwrap();
+
+    let ranked: Vec<_> = results.iter().map(|result| result.pr_number).collect();
+    assert_eq!(ranked, [3, 2, 1]);
+    assert!(results.windows(2).all(|pair| pair[0].bm25_score == pair[1].bm25_score), "{:?}", results);
+    assert_eq!(results[1].recency_factor, Some(0.5));
+}
+
+#[test]
+fn semantic_weight_reorders_lexical_matches() {
+    let cache = temp_dir("semantic-weight");
+    let model = cache.join("model.vec");
+    std::fs::write(&model, "retry 1 0\nrequests 0 1\nbackoff 1 0.1\n").unwrap();
+    let prs = || {
+        let mut wordy = pull_request(1, "Retry retry retry requests", "");
+        wordy.description = "requests requests".to_string();
+        let mut backoff = pull_request(2, "Retry with backoff", "");
+        backoff.description = "backoff".to_string();
+        [wordy, backoff]
+    };
+    let ranked = |semantic_weight: f32| {
+        let config = SearchConfig {
+            embedding_model: Some(model.clone()),
+            semantic_weight,
+            ..SearchConfig::default()
+        };
+        let dir = cache.join(format!("index-{}", semantic_weight));
+        let mut index = PRSearchIndex::with_config(Vec::new(), &dir, config).unwrap();
+        index.import_prs(&prs()).unwrap();
+        let results = index.search("retry", 10).unwrap();
+        results.iter().map(|result| result.pr_number).collect::<Vec<_>>()
+    };
+
+    // BM25 favours the PR that repeats the word; its embedding leans
+    // towards `requests`, away from the query
+    assert_eq!(ranked(0.0), [1, 2]);
+    assert_eq!(ranked(1.0), [2, 1]);
+}
@@ -0,0 +1,220 @@
+mod support;
+
+use pr_similarity_search::api::ApiClient;
+use pr_similarity_search::config::SearchConfig;
+use pr_similarity_search::github::GitHubClient;
+use pr_similarity_search::sync_state::{SyncOptions, SyncState, MAX_FETCH_ATTEMPTS};
+use pr_similarity_search::{PRSearchIndex, RepoFilter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use support::{temp_dir, MockServer, Request, Response};
+
+/// PR `n` was merged (and last updated) at noon on May `n`.
+fn pull_request(number: u64) -> String {
+    pull_request_on(number, "closed", number, Some(number))
+}
+
+/// PR `number` as last updated at noon on May `updated`, and merged at noon
+/// on May `merged` if it was.
+fn pull_request_on(number: u64, state: &str, updated: u64, merged: Option<u64>) -> String {
+    let date = |day: u64| format!("2024-05-{:02}T12:00:00Z", day);
+    let sha = format!("{:040x}", number);
+    format!(
+        r#"{{
+            "url": "https://api.github.com/repos/acme/widgets/pulls/{n}",
+            "id": {id},
+            "number": {n},
+            "state": "{state}",
+            "title": "Change {n}",
+            "body": "",
+            "html_url": "https://github.com/acme/widgets/pull/{n}",
+            "created_at": "{created}",
+            "updated_at": "{updated}",
+            "merged_at": {merged},
+            "head": {{"ref": "change-{n}", "sha": "{sha}"}},
+            "base": {{"ref": "main", "sha": "0000000000000000000000000000000000000000"}}
+        }}"#,
+        n = number,
+        id = 1000 + number,
+        state = state,
+        created = date(1),
+        updated = date(updated),
+        merged = merged.map_or("null".to_string(), |day| format!("\"{}\"", date(day))),
+        sha = sha,
+    )
+}
+
+/// A repository whose PRs 1..=`merged` are merged, served over REST.
+fn repository(merged: Arc<AtomicU64>) -> impl Fn(&Request) -> Response {
+    move |request| {
+        let path = request.path.split('?').next().unwrap();
+        let Some(route) = path.strip_prefix("/repos/acme/widgets/") else {
+            return Response::status(404).body(r#"{"message": "Not Found"}"#);
+        };
+        let parts: Vec<_> = route.split('/').collect();
+        match parts.as_slice() {
+            ["pulls"] => {
+                // Most recently updated first, as requested
+                let prs: Vec<_> = (1..=merged.load(Ordering::SeqCst)).rev().map(pull_request).collect();
+                Response::ok(format!("[{}]", prs.join(",")))
+            }
+            ["pulls", n] if request.header("accept") == Some("application/vnd.github.v3.diff") => {
+                Response::ok(format!("diff --git a/change{n}.rs b/change{n}.rs\n", n = n))
+            }
+            ["pulls", n] => Response::ok(pull_request(n.parse().unwrap())),
+            ["pulls", _, "commits" | "comments" | "reviews"] | ["issues", _, "comments"] => Response::ok("[]"),
+            ["actions", "runs"] => Response::ok(r#"{"total_count": 0, "workflow_runs": []}"#),
+            ["commits", _, "check-runs"] => Response::ok(r#"{"total_count": 0, "check_runs": []}"#),
+            ["commits", _, "status"] => Response::ok(r#"{"state": "pending", "total_count": 0, "statuses": []}"#),
+            _ => Response::status(404).body(r#"{"message": "Not Found"}"#),
+        }
+    }
+}
+
+fn open_index(server: &MockServer, cache: &std::path::Path) -> PRSearchIndex {
+    let api = ApiClient::new("test-token".to_string(), &server.url).unwrap();
+    let client = GitHubClient::from_api(api, "acme".to_string(), "widgets".to_string());
+    let mut index = PRSearchIndex::with_config(vec![client], cache, SearchConfig::default()).unwrap();
+    index.set_sync_options(SyncOptions {
+        graphql_batch_size: 0,
+        ..SyncOptions::default()
+    });
+    index
+}
+
+fn numbers(prs: &[pr_similarity_search::github::PullRequestSummary]) -> Vec<u64> {
+    let mut numbers: Vec<_> = prs.iter().map(|pr| pr.number).collect();
+    numbers.sort();
+    numbers
+}
+
+#[tokio::test]
+async fn incremental_sync_fetches_every_pr_merged_since_watermark() {
+    let merged = Arc::new(AtomicU64::new(3));
+    let server = MockServer::start(repository(merged.clone())).await;
+    let cache = temp_dir("incremental-sync");
+    let limit = 2;
+
+    let first = open_index(&server, &cache).load_recent_prs(limit, false).await.unwrap();
+    assert_eq!(numbers(&first), [2, 3]);
+
+    // More PRs merge than one sync's limit
+    merged.store(8, Ordering::SeqCst);
+    let second = open_index(&server, &cache).load_recent_prs(limit, false).await.unwrap();
+    assert_eq!(numbers(&second), [4, 5, 6, 7, 8]);
+
+    let watermark = SyncState::load(&cache).unwrap().watermark("acme/widgets").unwrap();
+    assert_eq!(watermark.to_rfc3339(), "2024-05-08T12:00:00+00:00");
+
+    let third = open_index(&server, &cache).load_recent_prs(limit, false).await.unwrap();
+    assert!(third.is_empty());
+}
+
+#[tokio::test]
+async fn pr_that_keeps_failing_holds_the_watermark_only_for_a_while() {
+    let merged = Arc::new(AtomicU64::new(3));
+    let serve = repository(merged.clone());
+    // PR 5 was deleted after it merged
+    let server = MockServer::start(move |request| match request.path.as_str() {
+        "/repos/acme/widgets/pulls/5" => Response::status(404).body(r#"{"message": "Not Found"}"#),
+        _ => serve(request),
+    })
+    .await;
+    let cache = temp_dir("failing-sync");
+    let watermark = || SyncState::load(&cache).unwrap().watermark("acme/widgets").unwrap().to_rfc3339();
+
+    open_index(&server, &cache).load_recent_prs(2, false).await.unwrap();
+    merged.store(8, Ordering::SeqCst);
+
+    let mut index = open_index(&server, &cache);
+    let synced = index.load_recent_prs(2, false).await.unwrap();
+    assert_eq!(numbers(&synced), [4, 6, 7, 8]);
+    assert!(index.get_pr_in(8, &RepoFilter::All).unwrap().is_some());
+    assert!(index.get_pr_in(5, &RepoFilter::All).unwrap().is_none());
+    drop(index);
+    assert_eq!(watermark(), "2024-05-04T12:00:00+00:00");
+
+    // Later syncs retry it, and so the PRs after it, until it is given up on
+    for attempt in 2..=MAX_FETCH_ATTEMPTS {
+        let synced = open_index(&server, &cache).load_recent_prs(2, false).await.unwrap();
+        assert_eq!(numbers(&synced), [6, 7, 8]);
+        assert_eq!(SyncState::load(&cache).unwrap().failed_attempts("acme/widgets", 5), attempt);
+    }
+    assert_eq!(watermark(), "2024-05-08T12:00:00+00:00");
+
+    let synced = open_index(&server, &cache).load_recent_prs(2, false).await.unwrap();
+    assert!(synced.is_empty());
+}
+
+/// `(number, state, updated, merged)`, as taken by `pull_request_on`.
+type PrDays = (u64, &'static str, u64, Option<u64>);
+
+/// A repository serving `prs`, most recently updated first.
+fn repository_of(prs: Arc<Mutex<Vec<PrDays>>>) -> impl Fn(&Request) -> Response {
+    let rest = repository(Arc::new(AtomicU64::new(0)));
+    move |request| {
+        let mut prs = prs.lock().unwrap().clone();
+        prs.sort_by_key(|&(_, _, updated, _)| std::cmp::Reverse(updated));
+        if request.path.contains("state=closed") {
+            prs.retain(|&(_, state, _, _)| state == "closed");
+        }
+        let json: Vec<_> = prs
+            .iter()
+            .map(|&(number, state, updated, merged)| (number, pull_request_on(number, state, updated, merged)))
+            .collect();
+
+        let path = request.path.split('?').next().unwrap();
+        let parts: Vec<_> = path.trim_start_matches("/repos/acme/widgets/").split('/').collect();
+        match parts.as_slice() {
+            ["pulls"] => {
+                let listed: Vec<_> = json.into_iter().map(|(_, json)| json).collect();
+                Response::ok(format!("[{}]", listed.join(",")))
+            }
+            ["pulls", n] if request.header("accept") != Some("application/vnd.github.v3.diff") => {
+                let (_, json) = json.into_iter().find(|(number, _)| number.to_string() == *n).unwrap();
+                Response::ok(json)
+            }
+            _ => rest(request),
+        }
+    }
+}
+
+#[tokio::test]
+async fn unmerged_prs_are_fetched_again_only_when_updated() {
+    let prs = Arc::new(Mutex::new(vec![
+        (1, "closed", 1, Some(1)),
+        (2, "closed", 2, Some(2)),
+        (20, "open", 10, None),
+        (21, "closed", 11, None),
+    ]));
+    let server = MockServer::start(repository_of(prs.clone())).await;
+    let cache = temp_dir("unmerged-sync");
+    let sync = || async {
+        let mut index = open_index(&server, &cache);
+        index.set_sync_options(SyncOptions {
+            graphql_batch_size: 0,
+            include_unmerged: true,
+            ..SyncOptions::default()
+        });
+        numbers(&index.load_recent_prs(10, false).await.unwrap())
+    };
+
+    assert_eq!(sync().await, [1, 2, 20, 21]);
+    let state = SyncState::load(&cache).unwrap();
+    assert_eq!(state.watermark("acme/widgets").unwrap().to_rfc3339(), "2024-05-02T12:00:00+00:00");
+    assert_eq!(state.unmerged_watermark("acme/widgets").unwrap().to_rfc3339(), "2024-05-11T12:00:00+00:00");
+
+    // No merges since, and the open PR hasn't changed
+    assert!(sync().await.is_empty());
+
+    prs.lock().unwrap()[2].2 = 12;
+    assert_eq!(sync().await, [20]);
+
+    // Merging the open PR moves it to the merge watermark
+    prs.lock().unwrap()[2] = (20, "closed", 13, Some(13));
+    assert_eq!(sync().await, [20]);
+    let state = SyncState::load(&cache).unwrap();
+    assert_eq!(state.watermark("acme/widgets").unwrap().to_rfc3339(), "2024-05-13T12:00:00+00:00");
+    assert_eq!(state.unmerged_watermark("acme/widgets").unwrap().to_rfc3339(), "2024-05-12T12:00:00+00:00");
+    assert!(sync().await.is_empty());
+}